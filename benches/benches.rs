@@ -4,7 +4,10 @@ extern crate test;
 use test::Bencher;
 
 use fuzzy_search::{
-    automata::LevenshteinAutomata, basic::fuzzy_search, bk::BkTree, distance::levenshtein,
+    automata::{LazyLevenshteinAutomata, LevenshteinAutomata},
+    basic::fuzzy_search,
+    bk::BkTree,
+    distance::levenshtein,
     symspell::SymSpell,
 };
 
@@ -20,6 +23,38 @@ fn bench_levenshtein_automata(b: &mut Bencher) {
     });
 }
 
+#[bench]
+fn bench_levenshtein_automata_search(b: &mut Bencher) {
+    let choices = load_choices(true);
+    let automata = LevenshteinAutomata::new("foodstuffs", 3);
+    b.iter(|| {
+        let _ = automata.fuzzy_search(&choices);
+    });
+}
+
+#[bench]
+fn bench_levenshtein_automata_new(b: &mut Bencher) {
+    b.iter(|| {
+        let _ = LevenshteinAutomata::new("foodstuffs", 2);
+    });
+}
+
+#[bench]
+fn bench_levenshtein_automata_eager_k4(b: &mut Bencher) {
+    let choices = load_choices(true);
+    b.iter(|| {
+        let _ = LevenshteinAutomata::new("foodstuffs", 4).fuzzy_search(&choices);
+    });
+}
+
+#[bench]
+fn bench_levenshtein_automata_lazy_k4(b: &mut Bencher) {
+    let choices = load_choices(true);
+    b.iter(|| {
+        let _ = LazyLevenshteinAutomata::new("foodstuffs", 4, 10_000).fuzzy_search(&choices);
+    });
+}
+
 #[bench]
 fn bench_levenshtein(b: &mut Bencher) {
     let choices = load_choices(false);
@@ -40,6 +75,14 @@ fn bench_bk_tree(b: &mut Bencher) {
     })
 }
 
+#[bench]
+fn bench_par_bk_tree(b: &mut Bencher) {
+    let bk = BkTree::from_par_iter(levenshtein, load_choices(false));
+    b.iter(|| {
+        let _: Vec<String> = bk.par_fuzzy_search("food", 2);
+    })
+}
+
 #[bench]
 fn bench_symspell(b: &mut Bencher) {
     let mut sym = SymSpell::new(levenshtein, 2);
@@ -1,43 +1,280 @@
+mod compact;
 mod dfa;
+mod dot;
+mod incremental;
+mod lazy;
+mod multi;
 mod nfa;
+mod parametric;
+mod pattern;
+mod regex;
+mod sorted;
+mod utf8;
+mod walk;
+pub use incremental::IncrementalSearch;
+pub use lazy::LazyLevenshteinAutomata;
+pub use multi::MultiLevenshteinAutomata;
+pub use pattern::FuzzyPattern;
+pub use regex::{ApproximateRegex, TextMatch};
+pub use sorted::{SortedChoices, SortedTerms};
+pub use utf8::Utf8Dfa;
 
-use crate::automata::dfa::Dfa;
+use std::fmt;
+
+use anyhow::{ensure, Result};
+
+use crate::automata::compact::CompactDfa;
 use crate::automata::nfa::Nfa;
+use crate::automata::parametric::ParametricDfa;
+use crate::automata::walk::Walk;
+
+/// An automaton over `char`s that can be driven one character at a time,
+/// e.g. to intersect it with a trie, an FST or a database cursor.
+pub trait Automaton {
+    type State: Clone;
+
+    fn start(&self) -> Self::State;
+
+    /// The state reached from `state` by reading `ch`.
+    fn accept(&self, state: &Self::State, ch: char) -> Self::State;
+
+    /// Whether the characters read so far are accepted.
+    fn is_match(&self, state: &Self::State) -> bool;
+
+    /// Whether some continuation of the characters read so far can still be accepted.
+    /// Once this is false, the whole subtree below the current prefix can be skipped.
+    fn can_match(&self, state: &Self::State) -> bool;
+}
 
 // Ref. http://blog.notdot.net/2010/07/Damn-Cool-Algorithms-Levenshtein-Automata
-#[derive(Debug)]
-pub struct LevenshteinAutomata {
-    dfa: Dfa,
+/// `LevenshteinAutomata` is `Send` and `Sync` if its equivalence function is.
+pub struct LevenshteinAutomata<F = fn(char) -> char> {
+    dfa: CompactDfa,
+    /// What the automaton was built from, to rebuild its NFA for [`LevenshteinAutomata::nfa_dot`].
+    query: String,
+    max_edits: usize,
+    prefix: bool,
+    /// Maps each character to the representative of its equivalence class.
+    fold: Option<F>,
 }
 
 impl LevenshteinAutomata {
+    /// Up to `max_edits = 3`, the automaton is instantiated from
+    /// precomputed parametric tables in time linear in the length of `query`;
+    /// above that, it is built by subset construction of the NFA.
     pub fn new(query: &str, max_edits: usize) -> Self {
-        let nfa = Nfa::new(query, max_edits);
-        Self { dfa: nfa.to_dfa() }
+        Self {
+            dfa: Self::build(query, max_edits),
+            query: query.to_string(),
+            max_edits,
+            prefix: false,
+            fold: None,
+        }
     }
 
-    pub fn fuzzy_search(&self, choices: &[String]) -> Vec<String> {
-        let mut ret = vec![];
-        let mut maybe_string = self.dfa.next_valid_string(String::from('\0'));
-        while let Some(string) = maybe_string {
-            match choices.binary_search(&string) {
-                Ok(pos) | Err(pos) => match choices.get(pos) {
-                    Some(next) => {
-                        let mut next = next.to_string();
-                        if string == next {
-                            ret.push(string);
-                            next.push('\0');
-                        }
-                        maybe_string = self.dfa.next_valid_string(next);
-                    }
-                    _ => break,
-                },
-            }
+    fn build(query: &str, max_edits: usize) -> CompactDfa {
+        match ParametricDfa::get(max_edits) {
+            Some(parametric) => CompactDfa::from_dfa(&parametric.build_dfa(query)),
+            None => CompactDfa::new(&Nfa::new(query, max_edits).to_dfa(usize::MAX).unwrap()),
+        }
+    }
+
+    #[cfg(test)]
+    fn from_nfa(query: &str, max_edits: usize) -> Self {
+        Self {
+            dfa: CompactDfa::new(&Nfa::new(query, max_edits).to_dfa(usize::MAX).unwrap()),
+            query: query.to_string(),
+            max_edits,
+            prefix: false,
+            fold: None,
+        }
+    }
+
+    /// Builds an automaton for type-ahead search, accepting every string
+    /// that has a prefix within `max_edits` of `query`,
+    /// e.g. "fuud" matches "foodstuffs" with `max_edits = 2`.
+    pub fn new_prefix(query: &str, max_edits: usize) -> Self {
+        Self {
+            dfa: CompactDfa::new(
+                &Nfa::new_prefix(query, max_edits)
+                    .to_dfa(usize::MAX)
+                    .unwrap(),
+            ),
+            query: query.to_string(),
+            max_edits,
+            prefix: true,
+            fold: None,
+        }
+    }
+}
+
+impl<F: Fn(char) -> char> LevenshteinAutomata<F> {
+    /// Same as [`LevenshteinAutomata::new`], where characters with the same image
+    /// under `fold` match for free, e.g. with `|ch| ch.to_ascii_lowercase()`
+    /// "Food" matches "FOOD" with `max_edits = 0`.
+    ///
+    /// Matches are still reported in their original form.
+    /// Searching sorted choices runs the automaton on the terms under the prefixes
+    /// that can still match, as the characters of each class are not known in advance.
+    pub fn with_equivalence(query: &str, max_edits: usize, fold: F) -> Self {
+        let query: String = query.chars().map(&fold).collect();
+        Self {
+            dfa: LevenshteinAutomata::build(&query, max_edits),
+            query,
+            max_edits,
+            prefix: false,
+            fold: Some(fold),
+        }
+    }
+
+    /// Same as [`LevenshteinAutomata::new_prefix`] with the equivalence of
+    /// [`LevenshteinAutomata::with_equivalence`].
+    pub fn prefix_with_equivalence(query: &str, max_edits: usize, fold: F) -> Self {
+        let query: String = query.chars().map(&fold).collect();
+        Self {
+            dfa: CompactDfa::new(
+                &Nfa::new_prefix(&query, max_edits)
+                    .to_dfa(usize::MAX)
+                    .unwrap(),
+            ),
+            query,
+            max_edits,
+            prefix: true,
+            fold: Some(fold),
+        }
+    }
+
+    /// Minimum number of edits already spent to reach `state`,
+    /// or `None` if nothing can match from it.
+    ///
+    /// For a matching state, the distance of the input to the query is at least this.
+    pub fn edits(&self, state: &usize) -> Option<usize> {
+        match self.can_match(state) {
+            true => Some(self.dfa.min_edits(*state as u32)),
+            false => None,
         }
-        ret
+    }
+
+    /// `choices` must be sorted; use [`SortedChoices`] to have that checked,
+    /// or [`LevenshteinAutomata::fuzzy_search_sorted`] for other term sources.
+    pub fn fuzzy_search(&self, choices: &[String]) -> Vec<String> {
+        self.fuzzy_search_sorted(choices)
+    }
+
+    pub fn fuzzy_search_sorted<T: SortedTerms + ?Sized>(&self, choices: &T) -> Vec<String> {
+        self.fuzzy_search_with_edits(choices)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Same as [`LevenshteinAutomata::fuzzy_search_sorted`], along with the distance
+    /// between the query and each match, read from the final state it ends in.
+    ///
+    /// For a prefix automaton, that is the distance to the closest prefix of the match.
+    pub fn fuzzy_search_with_edits<T: SortedTerms + ?Sized>(
+        &self,
+        choices: &T,
+    ) -> Vec<(String, usize)> {
+        let matches = match &self.fold {
+            Some(fold) => self.dfa.search_folded(choices, fold),
+            None => self.dfa.search(choices),
+        };
+        matches
+            .into_iter()
+            .map(|(term, state)| (term, self.dfa.final_edits(state).unwrap()))
+            .collect()
+    }
+
+    /// Distance between the query and the input read to reach `state`,
+    /// or `None` if it does not match.
+    pub fn distance(&self, state: &usize) -> Option<usize> {
+        self.dfa.final_edits(*state as u32)
+    }
+
+    /// Renders the NFA of the automaton in the Graphviz DOT language,
+    /// e.g. to inspect it with `dot -Tsvg`.
+    /// States are `(index in the query, edits)`, and edges are `Any`, `Epsilon`
+    /// or `Input` of a character of the query.
+    ///
+    /// With an equivalence function, the query is shown folded.
+    pub fn nfa_dot(&self) -> String {
+        let nfa = match self.prefix {
+            true => Nfa::new_prefix(&self.query, self.max_edits),
+            false => Nfa::new(&self.query, self.max_edits),
+        };
+        nfa.to_dot()
+    }
+
+    /// Compiles the automaton to run over UTF-8 bytes.
+    ///
+    /// Fails if it has an equivalence function, which cannot be compiled.
+    pub fn to_utf8(&self) -> Result<Utf8Dfa> {
+        ensure!(
+            self.fold.is_none(),
+            "an automaton with an equivalence function cannot be compiled to bytes"
+        );
+        Ok(Utf8Dfa::new(&self.dfa))
+    }
+
+    /// Renders the minimized DFA of the automaton in the Graphviz DOT language.
+    /// States are numbered as in [`Automaton`], and final ones are labelled
+    /// with their distance to the query.
+    pub fn dfa_dot(&self) -> String {
+        self.dfa.to_dot()
+    }
+}
+
+impl<F> fmt::Debug for LevenshteinAutomata<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LevenshteinAutomata")
+            .field("dfa", &self.dfa)
+            .field("query", &self.query)
+            .field("max_edits", &self.max_edits)
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: Fn(char) -> char> Automaton for LevenshteinAutomata<F> {
+    type State = usize;
+
+    fn start(&self) -> usize {
+        self.dfa.start as usize
+    }
+
+    fn accept(&self, state: &usize, ch: char) -> usize {
+        let ch = match &self.fold {
+            Some(fold) => fold(ch),
+            None => ch,
+        };
+        self.dfa.step(&(*state as u32), ch) as usize
+    }
+
+    fn is_match(&self, state: &usize) -> bool {
+        self.dfa.is_final(&(*state as u32))
+    }
+
+    fn can_match(&self, state: &usize) -> bool {
+        *state != self.dfa.dead as usize
     }
 }
 
+/// All the strings of at most `max_len` characters from `alphabet`, sorted.
+#[cfg(test)]
+fn all_strings(alphabet: &[char], max_len: usize) -> Vec<String> {
+    let (mut strings, mut longest) = (vec![String::new()], vec![String::new()]);
+    for _ in 0..max_len {
+        longest = longest
+            .iter()
+            .flat_map(|s| alphabet.iter().map(move |ch| format!("{}{}", s, ch)))
+            .collect();
+        strings.extend(longest.iter().cloned());
+    }
+    strings.sort();
+    strings
+}
+
 #[cfg(test)]
 mod tests {
     mod fuzzy_search {
@@ -46,12 +283,198 @@ mod tests {
         #[test]
         fn test() {
             let a = LevenshteinAutomata::new("kitten", 2);
+            assert_eq!(a.fuzzy_search(&["sitting".into()]), Vec::<String>::new());
+            let a = LevenshteinAutomata::new("kitten", 3);
+            assert_eq!(a.fuzzy_search(&["sitting".into()]), vec!["sitting"]);
+        }
+
+        #[test]
+        fn test_empty() {
+            let a = LevenshteinAutomata::new("", 1);
             assert_eq!(
-                a.fuzzy_search(&vec!["sitting".into()]),
-                Vec::<String>::new()
+                a.fuzzy_search(&["".into(), "a".into(), "ab".into()]),
+                vec!["", "a"]
             );
-            let a = LevenshteinAutomata::new("kitten", 3);
-            assert_eq!(a.fuzzy_search(&vec!["sitting".into()]), vec!["sitting"]);
+        }
+
+        #[test]
+        fn test_prefix() {
+            let choices =
+                ["fodder", "food", "foodstuffs", "foot", "fuddle", "good"].map(String::from);
+            let a = LevenshteinAutomata::new_prefix("fuud", 1);
+            assert_eq!(a.fuzzy_search(&choices), vec!["fuddle"]);
+            let a = LevenshteinAutomata::new_prefix("fuud", 2);
+            assert_eq!(
+                a.fuzzy_search(&choices),
+                vec!["fodder", "food", "foodstuffs", "fuddle"]
+            );
+            let a = LevenshteinAutomata::new_prefix("", 0);
+            assert_eq!(a.fuzzy_search(&choices), choices.to_vec());
+        }
+
+        #[test]
+        fn test_unicode() {
+            let a = LevenshteinAutomata::new("café", 0);
+            assert_eq!(a.fuzzy_search(&["café".into()]), vec!["café"]);
+        }
+    }
+
+    mod parametric {
+        use crate::{
+            automata::{all_strings, Automaton, LevenshteinAutomata},
+            distance::levenshtein,
+        };
+
+        #[test]
+        fn test() {
+            let choices = all_strings(&['a', 'b', 'c'], 5);
+
+            for query in ["", "a", "ab", "abc", "cabba", "bbbbbb"] {
+                for max_edits in 0..=3 {
+                    let parametric = LevenshteinAutomata::new(query, max_edits);
+                    let nfa = LevenshteinAutomata::from_nfa(query, max_edits);
+                    let expected: Vec<String> = choices
+                        .iter()
+                        .filter(|c| levenshtein(query, c) <= max_edits)
+                        .cloned()
+                        .collect();
+                    assert_eq!(parametric.fuzzy_search(&choices), expected);
+                    assert_eq!(nfa.fuzzy_search(&choices), expected);
+
+                    for choice in choices.iter() {
+                        let run = |a: &LevenshteinAutomata| {
+                            choice.chars().fold(a.start(), |s, ch| a.accept(&s, ch))
+                        };
+                        assert_eq!(parametric.edits(&run(&parametric)), nfa.edits(&run(&nfa)));
+                        let distance = levenshtein(query, choice);
+                        let expected = (distance <= max_edits).then_some(distance);
+                        assert_eq!(parametric.distance(&run(&parametric)), expected);
+                        assert_eq!(nfa.distance(&run(&nfa)), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    mod with_equivalence {
+        use crate::{
+            automata::{Automaton, LevenshteinAutomata},
+            distance::levenshtein,
+        };
+
+        fn fold(ch: char) -> char {
+            match ch.to_lowercase().next().unwrap() {
+                'à' | 'â' => 'a',
+                'é' | 'è' | 'ê' => 'e',
+                ch => ch,
+            }
+        }
+
+        #[test]
+        fn test() {
+            let mut choices = ["Cafe", "café", "CAFÉS", "cafard", "chafe", "Coffee", "kafe"]
+                .map(String::from)
+                .to_vec();
+            choices.sort();
+            for max_edits in 0..=4 {
+                let a = LevenshteinAutomata::with_equivalence("CAFE", max_edits, fold);
+                let expected: Vec<(String, usize)> = choices
+                    .iter()
+                    .map(|c| {
+                        let folded: String = c.chars().map(fold).collect();
+                        (c.clone(), levenshtein("cafe", &folded))
+                    })
+                    .filter(|(_, distance)| *distance <= max_edits)
+                    .collect();
+                assert_eq!(a.fuzzy_search_with_edits(&choices), expected);
+            }
+
+            let a = LevenshteinAutomata::with_equivalence("CAFE", 0, fold);
+            let state = "Café".chars().fold(a.start(), |s, ch| a.accept(&s, ch));
+            assert_eq!(a.distance(&state), Some(0));
+        }
+
+        #[test]
+        fn test_prefix() {
+            let choices = ["Cafeteria", "café", "cake"].map(String::from).to_vec();
+            let a = LevenshteinAutomata::prefix_with_equivalence("CAFÉ", 0, fold);
+            assert_eq!(a.fuzzy_search(&choices), vec!["Cafeteria", "café"]);
+        }
+    }
+
+    mod fuzzy_search_sorted {
+        use std::collections::{BTreeMap, BTreeSet};
+
+        use crate::automata::{LevenshteinAutomata, SortedChoices};
+
+        #[test]
+        fn test() {
+            let choices = ["flood", "fold", "food", "foods", "good", "mood"].map(String::from);
+            let expected = vec!["fold", "food"];
+            let a = LevenshteinAutomata::new("fod", 1);
+
+            let set = BTreeSet::from_iter(choices.iter().cloned());
+            assert_eq!(a.fuzzy_search_sorted(&set), expected);
+            let map = BTreeMap::from_iter(choices.iter().cloned().map(|c| (c, ())));
+            assert_eq!(a.fuzzy_search_sorted(&map), expected);
+            let sorted = SortedChoices::new(choices.to_vec()).unwrap();
+            assert_eq!(a.fuzzy_search_sorted(&sorted), expected);
+            assert_eq!(
+                a.fuzzy_search_with_edits(&sorted),
+                vec![("fold".into(), 1), ("food".into(), 1)]
+            );
+        }
+
+        #[test]
+        fn test_unsorted() {
+            let choices = vec!["food".to_string(), "flood".to_string()];
+            assert!(SortedChoices::new(choices.clone()).is_err());
+            assert_eq!(
+                SortedChoices::sorted(choices).into_inner(),
+                vec!["flood", "food"]
+            );
+        }
+    }
+
+    mod dot {
+        use crate::automata::{Automaton, LevenshteinAutomata};
+
+        #[test]
+        fn test() {
+            let a = LevenshteinAutomata::new("ab", 1);
+            let nfa = a.nfa_dot();
+            assert!(nfa.starts_with("digraph nfa {"));
+            assert!(nfa.contains(r#""1,0" -> "2,0" [label="Input('b')"];"#));
+            assert!(nfa.contains(r#""2,1" [label="(2, 1)", shape=doublecircle];"#));
+            let dfa = a.dfa_dot();
+            assert!(dfa.starts_with("digraph dfa {"));
+            assert!(dfa.contains(&format!("start -> {};", a.start())));
+
+            let a =
+                LevenshteinAutomata::prefix_with_equivalence("AB", 0, |ch| ch.to_ascii_lowercase());
+            assert!(a.nfa_dot().contains(r#""2,0" -> "2,0" [label="Any"];"#));
+        }
+    }
+
+    mod automaton {
+        use crate::automata::{Automaton, LevenshteinAutomata};
+
+        fn run(a: &LevenshteinAutomata, input: &str) -> usize {
+            input
+                .chars()
+                .fold(a.start(), |state, ch| a.accept(&state, ch))
+        }
+
+        #[test]
+        fn test() {
+            let a = LevenshteinAutomata::new("food", 1);
+            assert!(a.is_match(&run(&a, "food")));
+            assert!(a.is_match(&run(&a, "fod")));
+            assert!(!a.is_match(&run(&a, "fo")));
+            assert!(a.can_match(&run(&a, "fo")));
+            assert_eq!(a.edits(&run(&a, "fx")), Some(1));
+            assert!(!a.can_match(&run(&a, "fxx")));
+            assert_eq!(a.edits(&run(&a, "fxx")), None);
         }
     }
 }
@@ -0,0 +1,359 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+};
+
+use crate::automata::{
+    dfa::Dfa,
+    dot::quote,
+    walk::{next_char, Walk},
+};
+
+const NOT_FINAL: u32 = u32::MAX;
+
+/// A minimized [`Dfa`] with dense `u32` state ids,
+/// where the edges of each state are stored contiguously and sorted by character,
+/// so a transition is a binary search over a few characters instead of hash lookups.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CompactDfa {
+    pub start: u32,
+    /// The state from which nothing can match; it has no edges.
+    pub dead: u32,
+    /// The edges of state `s` are `edges[offsets[s]..offsets[s + 1]]`.
+    offsets: Vec<u32>,
+    edges: Vec<(char, u32)>,
+    /// The transition for characters without an edge.
+    defaults: Vec<u32>,
+    /// The distance to the query of final states, `NOT_FINAL` for the others.
+    final_edits: Vec<u32>,
+    min_edits: Vec<u32>,
+}
+
+impl CompactDfa {
+    /// Minimizes `dfa` by partition refinement and lays out the result.
+    ///
+    /// States are only merged if they have the same final edit count,
+    /// so distances are preserved; `min_edits` of a merged state is
+    /// the minimum of its members, which is still a lower bound.
+    pub fn new(dfa: &Dfa) -> Self {
+        let (edges, defaults) = Self::sorted_edges(dfa);
+        let state_count = edges.len();
+        // Start from the states grouped by final edit count,
+        // and split classes until all members agree on the classes of their targets.
+        // Edges leading to the same class as the default one are left out of the signature,
+        // so that states with different explicit characters can still be merged.
+        let mut classes: Vec<usize> = (0..state_count)
+            .map(|state| dfa.final_edits.get(&state).map_or(0, |edits| edits + 1))
+            .collect();
+        let mut class_count = 0;
+        loop {
+            let mut ids = HashMap::new();
+            let next: Vec<usize> = (0..state_count)
+                .map(|state| {
+                    let default = classes[defaults[state]];
+                    let signature = (
+                        classes[state],
+                        default,
+                        edges[state]
+                            .iter()
+                            .map(|(ch, target)| (*ch, classes[*target]))
+                            .filter(|(_, class)| *class != default)
+                            .collect::<Vec<_>>(),
+                    );
+                    let len = ids.len();
+                    *ids.entry(signature).or_insert(len)
+                })
+                .collect();
+            classes = next;
+            if ids.len() == class_count {
+                break;
+            }
+            class_count = ids.len();
+        }
+        Self::layout(dfa, &edges, &defaults, &classes, class_count)
+    }
+
+    /// Lays out `dfa` without minimizing it, one state per reachable state of `dfa`.
+    ///
+    /// Much cheaper than [`CompactDfa::new`], for automata that are already
+    /// (close to) minimal, such as those of [`ParametricDfa`](crate::automata::parametric::ParametricDfa).
+    pub fn from_dfa(dfa: &Dfa) -> Self {
+        let (edges, defaults) = Self::sorted_edges(dfa);
+        let classes = Vec::from_iter(0..edges.len());
+        Self::layout(dfa, &edges, &defaults, &classes, edges.len())
+    }
+
+    /// The explicit edges of each state of `dfa` sorted by character, and its default target.
+    fn sorted_edges(dfa: &Dfa) -> (Vec<Vec<(char, usize)>>, Vec<usize>) {
+        let state_count = dfa.min_edits.len();
+        let edges = (0..state_count)
+            .map(|state| {
+                let mut edges = Vec::from_iter(
+                    dfa.transitions
+                        .0
+                        .get(&state)
+                        .into_iter()
+                        .flatten()
+                        .map(|(ch, target)| (*ch, *target)),
+                );
+                edges.sort_unstable();
+                edges
+            })
+            .collect();
+        let defaults = (0..state_count)
+            .map(|state| *dfa.any_transitions.get(&state).unwrap_or(&dfa.dead_id))
+            .collect();
+        (edges, defaults)
+    }
+
+    /// Lays out the states of `dfa` merged into `classes`, one state per class.
+    fn layout(
+        dfa: &Dfa,
+        edges: &[Vec<(char, usize)>],
+        defaults: &[usize],
+        classes: &[usize],
+        class_count: usize,
+    ) -> Self {
+        let state_count = edges.len();
+        // Number the classes breadth-first from the start state for locality.
+        let mut numbers = vec![u32::MAX; class_count];
+        let mut representatives = vec![];
+        let mut queue = VecDeque::from([dfa.start_id, dfa.dead_id]);
+        while let Some(state) = queue.pop_front() {
+            if numbers[classes[state]] != u32::MAX {
+                continue;
+            }
+            numbers[classes[state]] = representatives.len() as u32;
+            representatives.push(state);
+            queue.extend(edges[state].iter().map(|(_, target)| *target));
+            queue.push_back(defaults[state]);
+        }
+
+        let mut compact = Self {
+            start: numbers[classes[dfa.start_id]],
+            dead: numbers[classes[dfa.dead_id]],
+            offsets: vec![0],
+            ..Self::default()
+        };
+        for &state in representatives.iter() {
+            let default = numbers[classes[defaults[state]]];
+            compact
+                .edges
+                .extend(edges[state].iter().filter_map(|(ch, target)| {
+                    let target = numbers[classes[*target]];
+                    (target != default).then_some((*ch, target))
+                }));
+            compact.offsets.push(compact.edges.len() as u32);
+            compact.defaults.push(default);
+            compact.final_edits.push(
+                dfa.final_edits
+                    .get(&state)
+                    .map_or(NOT_FINAL, |edits| *edits as u32),
+            );
+            compact.min_edits.push(u32::MAX);
+        }
+        for state in 0..state_count {
+            let number = numbers[classes[state]];
+            if number != u32::MAX {
+                let min_edits = &mut compact.min_edits[number as usize];
+                *min_edits = (*min_edits).min(dfa.min_edits[state] as u32);
+            }
+        }
+        compact
+    }
+
+    /// The transitions of `state` whose target differs from [`CompactDfa::default_target`],
+    /// sorted by character.
+    pub fn edges(&self, state: u32) -> &[(char, u32)] {
+        let state = state as usize;
+        &self.edges[self.offsets[state] as usize..self.offsets[state + 1] as usize]
+    }
+
+    /// The target of `state` for characters without an edge.
+    pub fn default_target(&self, state: u32) -> u32 {
+        self.defaults[state as usize]
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.defaults.len()
+    }
+
+    pub fn final_edits(&self, state: u32) -> Option<usize> {
+        match self.final_edits[state as usize] {
+            NOT_FINAL => None,
+            edits => Some(edits as usize),
+        }
+    }
+
+    pub fn min_edits(&self, state: u32) -> usize {
+        self.min_edits[state as usize] as usize
+    }
+
+    /// Renders the DFA in the Graphviz DOT language, where final states are double circles
+    /// labelled with their edit count, and `Any` edges are taken by the other characters.
+    /// The dead state and the edges into it are left out.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph dfa {\n    rankdir=LR;\n    start [shape=point];\n");
+        let states = (0..self.defaults.len() as u32).filter(|state| *state != self.dead);
+        for state in states.clone() {
+            match self.final_edits(state) {
+                Some(edits) => writeln!(
+                    dot,
+                    "    {state} [label=\"{state} ({edits} edits)\", shape=doublecircle];"
+                ),
+                None => writeln!(dot, "    {state} [shape=circle];"),
+            }
+            .unwrap();
+        }
+        writeln!(dot, "    start -> {};", self.start).unwrap();
+        for state in states {
+            for (ch, target) in self.edges(state) {
+                if *target != self.dead {
+                    let label = quote(&format!("{:?}", ch));
+                    writeln!(dot, "    {state} -> {target} [label={label}];").unwrap();
+                }
+            }
+            let default = self.defaults[state as usize];
+            if default != self.dead {
+                writeln!(dot, "    {state} -> {default} [label=\"Any\"];").unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl Walk for CompactDfa {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.start
+    }
+
+    fn step(&self, state: &u32, ch: char) -> u32 {
+        let edges = self.edges(*state);
+        match edges.binary_search_by_key(&ch, |(c, _)| *c) {
+            Ok(pos) => edges[pos].1,
+            Err(_) => self.defaults[*state as usize],
+        }
+    }
+
+    fn is_final(&self, state: &u32) -> bool {
+        self.final_edits[*state as usize] != NOT_FINAL
+    }
+
+    fn is_dead(&self, state: &u32) -> bool {
+        *state == self.dead
+    }
+
+    fn find_next_edge(&self, state: &u32, ch: Option<char>) -> Option<char> {
+        let next = match ch {
+            Some(ch) => next_char(ch)?,
+            None => '\0',
+        };
+        if self.defaults[*state as usize] != self.dead {
+            return Some(next);
+        }
+        let edges = self.edges(*state);
+        let pos = edges.partition_point(|(c, _)| *c < next);
+        edges[pos..]
+            .iter()
+            .find(|(_, target)| *target != self.dead)
+            .map(|(c, _)| *c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod new {
+        use crate::automata::{compact::CompactDfa, nfa::Nfa, parametric::ParametricDfa};
+
+        #[test]
+        fn test() {
+            // Once the query is matched, the states of a prefix automaton
+            // only differ by edits that no longer matter.
+            let dfa = Nfa::new_prefix("kitten", 2).to_dfa(usize::MAX).unwrap();
+            assert!(CompactDfa::new(&dfa).defaults.len() < dfa.min_edits.len());
+
+            let dfa = Nfa::new("kitten", 2).to_dfa(usize::MAX).unwrap();
+            let compact = CompactDfa::new(&dfa);
+            // Minimization does not depend on how the automaton was built.
+            let parametric = CompactDfa::new(&ParametricDfa::get(2).unwrap().build_dfa("kitten"));
+            assert_eq!(parametric.defaults.len(), compact.defaults.len());
+
+            // Accepts everything with no edit: one state looping on itself, plus the dead one.
+            let compact = CompactDfa::new(&Nfa::new_prefix("", 0).to_dfa(usize::MAX).unwrap());
+            assert_eq!(compact.defaults, vec![compact.start, compact.dead]);
+            assert_eq!(compact.final_edits(compact.start), Some(0));
+        }
+    }
+
+    mod from_dfa {
+        use crate::automata::{compact::CompactDfa, parametric::ParametricDfa, walk::Walk};
+
+        #[test]
+        fn test() {
+            let run = |dfa: &CompactDfa, choice: &str| {
+                let state = choice.chars().fold(dfa.start(), |s, ch| dfa.step(&s, ch));
+                dfa.final_edits(state)
+            };
+            for max_edits in 0..=ParametricDfa::MAX_EDITS {
+                let dfa = ParametricDfa::get(max_edits).unwrap().build_dfa("kitten");
+                let (laid_out, minimized) = (CompactDfa::from_dfa(&dfa), CompactDfa::new(&dfa));
+                // Parametric automata are close to minimal already.
+                assert!(laid_out.state_count() <= minimized.state_count() * 5 / 4);
+                for choice in [
+                    "", "kitten", "sitting", "kitte", "kiten", "ktten", "mitten", "xyz",
+                ] {
+                    assert_eq!(run(&laid_out, choice), run(&minimized, choice));
+                }
+            }
+        }
+    }
+
+    mod to_dot {
+        use crate::automata::{compact::CompactDfa, nfa::Nfa};
+
+        #[test]
+        fn test() {
+            assert_eq!(
+                CompactDfa::new(&Nfa::new("a", 0).to_dfa(usize::MAX).unwrap()).to_dot(),
+                r#"digraph dfa {
+    rankdir=LR;
+    start [shape=point];
+    0 [shape=circle];
+    2 [label="2 (0 edits)", shape=doublecircle];
+    start -> 0;
+    0 -> 2 [label="'a'"];
+}
+"#
+            );
+            let dot = CompactDfa::new(&Nfa::new("a", 1).to_dfa(usize::MAX).unwrap()).to_dot();
+            assert!(dot.contains(r#"0 [label="0 (1 edits)", shape=doublecircle];"#));
+            assert!(dot.contains(r#"[label="Any"];"#));
+        }
+    }
+
+    mod next_valid_string {
+        use crate::automata::{compact::CompactDfa, nfa::Nfa, walk::Walk};
+
+        #[test]
+        fn test() {
+            let compact = CompactDfa::new(&Nfa::new_prefix("", 0).to_dfa(usize::MAX).unwrap());
+            // Surrogates are skipped.
+            assert_eq!(
+                compact.find_next_edge(&compact.start, Some('\u{D7FF}')),
+                Some('\u{E000}')
+            );
+            let compact = CompactDfa::new(&Nfa::new("a", 1).to_dfa(usize::MAX).unwrap());
+            let (string, _) = compact.next_valid_string("b".into()).unwrap();
+            assert_eq!(string, "b");
+            let (string, _) = compact.next_valid_string("b\0".into()).unwrap();
+            assert_eq!(string, "ba");
+            assert_eq!(
+                compact.next_valid_string("\u{10FFFF}\u{10FFFF}".into()),
+                None
+            );
+        }
+    }
+}
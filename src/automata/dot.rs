@@ -0,0 +1,18 @@
+/// Quotes `label` as a Graphviz DOT string.
+pub fn quote(label: &str) -> String {
+    format!("\"{}\"", label.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    mod quote {
+        use crate::automata::dot::quote;
+
+        #[test]
+        fn test() {
+            assert_eq!(quote("Any"), r#""Any""#);
+            assert_eq!(quote(r#"Input('"')"#), r#""Input('\"')""#);
+            assert_eq!(quote(r"Input('\\')"), r#""Input('\\\\')""#);
+        }
+    }
+}
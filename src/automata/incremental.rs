@@ -0,0 +1,169 @@
+use crate::automata::{LevenshteinAutomata, SortedTerms};
+
+/// Type-ahead search over sorted choices for a query typed one character at a time,
+/// matching the terms that have a prefix within `max_edits` of the query,
+/// as [`LevenshteinAutomata::new_prefix`].
+///
+/// Appending to the query can only remove such matches, so they are searched once
+/// with an automaton, and then refined as the query grows:
+/// each match keeps the distances between the query and each of its prefixes,
+/// which are updated for the new character instead of searching the choices again.
+#[derive(Debug)]
+pub struct IncrementalSearch<'a, T: ?Sized> {
+    choices: &'a T,
+    max_edits: usize,
+    query: String,
+    /// `None` while the query has at most `max_edits` characters, so every term matches.
+    matches: Option<Vec<Match>>,
+}
+
+#[derive(Debug)]
+struct Match {
+    term: String,
+    chars: Vec<char>,
+    /// The distance between the query and each prefix of the term, up to `max_edits + 1`.
+    column: Vec<usize>,
+}
+
+impl Match {
+    fn new(term: String, query: &str, max_edits: usize) -> Self {
+        let chars = Vec::from_iter(term.chars());
+        let column = (0..=chars.len()).map(|j| j.min(max_edits + 1)).collect();
+        let mut m = Self {
+            term,
+            chars,
+            column,
+        };
+        for ch in query.chars() {
+            m.push(ch, max_edits);
+        }
+        m
+    }
+
+    fn push(&mut self, ch: char, max_edits: usize) {
+        let mut diagonal = self.column[0];
+        self.column[0] = (diagonal + 1).min(max_edits + 1);
+        for j in 1..self.column.len() {
+            let above = self.column[j];
+            let substitution = diagonal + usize::from(self.chars[j - 1] != ch);
+            self.column[j] = substitution
+                .min(above + 1)
+                .min(self.column[j - 1] + 1)
+                .min(max_edits + 1);
+            diagonal = above;
+        }
+    }
+
+    /// The distance between the query and the closest prefix of the term.
+    fn edits(&self) -> usize {
+        *self.column.iter().min().unwrap()
+    }
+}
+
+impl<'a, T: SortedTerms + ?Sized> IncrementalSearch<'a, T> {
+    /// Starts with an empty query.
+    pub fn new(choices: &'a T, max_edits: usize) -> Self {
+        Self {
+            choices,
+            max_edits,
+            query: String::new(),
+            matches: None,
+        }
+    }
+
+    pub fn query(&self) -> &str {
+        &self.query
+    }
+
+    /// Appends `ch` to the query and refines the matches.
+    pub fn push(&mut self, ch: char) {
+        self.query.push(ch);
+        match &mut self.matches {
+            Some(matches) => {
+                for m in matches.iter_mut() {
+                    m.push(ch, self.max_edits);
+                }
+                matches.retain(|m| m.edits() <= self.max_edits);
+            }
+            None if self.query.chars().count() > self.max_edits => {
+                let matches = LevenshteinAutomata::new_prefix(&self.query, self.max_edits)
+                    .fuzzy_search_sorted(self.choices)
+                    .into_iter()
+                    .map(|term| Match::new(term, &self.query, self.max_edits))
+                    .collect();
+                self.matches = Some(matches);
+            }
+            None => (),
+        }
+    }
+
+    pub fn push_str(&mut self, string: &str) {
+        for ch in string.chars() {
+            self.push(ch);
+        }
+    }
+
+    pub fn fuzzy_search(&self) -> Vec<String> {
+        self.fuzzy_search_with_edits()
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// The matches of the query so far in order, along with the distance
+    /// between the query and the closest prefix of each one.
+    pub fn fuzzy_search_with_edits(&self) -> Vec<(String, usize)> {
+        match &self.matches {
+            Some(matches) => matches
+                .iter()
+                .map(|m| (m.term.clone(), m.edits()))
+                .collect(),
+            None => LevenshteinAutomata::new_prefix(&self.query, self.max_edits)
+                .fuzzy_search_with_edits(self.choices),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod push {
+        use crate::automata::{all_strings, IncrementalSearch, LevenshteinAutomata};
+
+        #[test]
+        fn test() {
+            let choices = all_strings(&['a', 'b', 'c'], 5);
+
+            for query in ["abcab", "cccc", "bacba"] {
+                for max_edits in 0..=3 {
+                    let mut search = IncrementalSearch::new(&choices, max_edits);
+                    for (i, ch) in query.char_indices() {
+                        search.push(ch);
+                        assert_eq!(search.query(), &query[..i + 1]);
+                        assert_eq!(
+                            search.fuzzy_search_with_edits(),
+                            LevenshteinAutomata::new_prefix(search.query(), max_edits)
+                                .fuzzy_search_with_edits(&choices)
+                        );
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_unicode() {
+            let choices = ["cafeteria", "café", "cafés", "coffee"].map(String::from);
+            let mut search = IncrementalSearch::new(choices.as_slice(), 1);
+            search.push_str("café");
+            assert_eq!(
+                search.fuzzy_search_with_edits(),
+                vec![
+                    ("cafeteria".into(), 1),
+                    ("café".into(), 0),
+                    ("cafés".into(), 0)
+                ]
+            );
+            search.push('s');
+            assert_eq!(search.fuzzy_search(), vec!["café", "cafés"]);
+        }
+    }
+}
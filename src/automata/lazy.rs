@@ -0,0 +1,300 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::automata::{
+    nfa::{Nfa, State},
+    walk::{next_char, Walk},
+    SortedTerms,
+};
+
+/// A Levenshtein automaton whose DFA states are only built when a search reaches them,
+/// for queries where [`LevenshteinAutomata`](crate::automata::LevenshteinAutomata)
+/// would build many states that a search never visits, e.g. long ones with many edits.
+///
+/// At most `max_states` states are kept, and are reused by later searches;
+/// once the cache is full, the other states are computed by simulating the NFA
+/// each time they are reached.
+///
+/// `LazyLevenshteinAutomata` is `Send` and `Sync`;
+/// searches share the cache behind a lock, so concurrent ones run one at a time.
+#[derive(Debug)]
+pub struct LazyLevenshteinAutomata {
+    nfa: Nfa,
+    max_states: usize,
+    cache: Mutex<Cache>,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    start: Option<LazyState>,
+    ids: HashMap<State, usize>,
+    states: Vec<CachedState>,
+}
+
+#[derive(Debug)]
+struct CachedState {
+    info: StateInfo,
+    /// Targets computed so far, for each of `info.chars` and then for the other characters.
+    next: Vec<Option<LazyState>>,
+}
+
+#[derive(Debug)]
+pub struct StateInfo {
+    positions: State,
+    /// The characters with their own transitions, in ascending order.
+    chars: Vec<char>,
+    /// Whether the other characters lead to a state that is not dead.
+    any: bool,
+    final_edits: Option<usize>,
+}
+
+/// A set of positions is either always cached or never,
+/// so simulated states only need to be compared with each other.
+impl PartialEq for StateInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.positions == other.positions
+    }
+}
+
+impl Eq for StateInfo {}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LazyState {
+    Cached(usize),
+    Simulated(Arc<StateInfo>),
+}
+
+impl LazyLevenshteinAutomata {
+    pub fn new(query: &str, max_edits: usize, max_states: usize) -> Self {
+        Self::from_nfa(Nfa::new(query, max_edits), max_states)
+    }
+
+    /// Accepts every string that has a prefix within `max_edits` of `query`,
+    /// as [`LevenshteinAutomata::new_prefix`](crate::automata::LevenshteinAutomata::new_prefix).
+    pub fn new_prefix(query: &str, max_edits: usize, max_states: usize) -> Self {
+        Self::from_nfa(Nfa::new_prefix(query, max_edits), max_states)
+    }
+
+    /// Determinizes any `nfa` lazily, e.g. that of an
+    /// [`ApproximateRegex`](crate::automata::ApproximateRegex).
+    pub(super) fn from_nfa(nfa: Nfa, max_states: usize) -> Self {
+        Self {
+            nfa,
+            max_states,
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Number of states built and cached so far.
+    pub fn cached_states(&self) -> usize {
+        self.cache().states.len()
+    }
+
+    /// `choices` must be sorted; see [`SortedChoices`](crate::automata::SortedChoices).
+    pub fn fuzzy_search(&self, choices: &[String]) -> Vec<String> {
+        self.fuzzy_search_sorted(choices)
+    }
+
+    pub fn fuzzy_search_sorted<T: SortedTerms + ?Sized>(&self, choices: &T) -> Vec<String> {
+        self.fuzzy_search_with_edits(choices)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Same as [`LazyLevenshteinAutomata::fuzzy_search_sorted`], along with the distance
+    /// between the query and each match.
+    pub fn fuzzy_search_with_edits<T: SortedTerms + ?Sized>(
+        &self,
+        choices: &T,
+    ) -> Vec<(String, usize)> {
+        let searcher = self.searcher();
+        searcher
+            .search(choices)
+            .into_iter()
+            .map(|(term, state)| (term, searcher.final_edits(&state).unwrap()))
+            .collect()
+    }
+
+    /// Locks the cache until the returned searcher is dropped.
+    pub(super) fn searcher(&self) -> Searcher<'_> {
+        Searcher {
+            automata: self,
+            cache: RefCell::new(self.cache()),
+        }
+    }
+
+    fn cache(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Holds the cache for a whole search, rather than locking it at every step.
+pub(super) struct Searcher<'a> {
+    automata: &'a LazyLevenshteinAutomata,
+    cache: RefCell<MutexGuard<'a, Cache>>,
+}
+
+impl Searcher<'_> {
+    fn info<R>(&self, state: &LazyState, f: impl FnOnce(&StateInfo) -> R) -> R {
+        match state {
+            LazyState::Cached(id) => f(&self.cache.borrow().states[*id].info),
+            LazyState::Simulated(info) => f(info),
+        }
+    }
+
+    pub(super) fn final_edits(&self, state: &LazyState) -> Option<usize> {
+        self.info(state, |info| info.final_edits)
+    }
+
+    /// Caches `positions` if they are not yet and there is room left.
+    fn intern(&self, positions: State) -> LazyState {
+        let mut cache = self.cache.borrow_mut();
+        if let Some(id) = cache.ids.get(&positions) {
+            return LazyState::Cached(*id);
+        }
+        let nfa = &self.automata.nfa;
+        let (chars, any) = nfa.edges(&positions);
+        let info = StateInfo {
+            final_edits: nfa.final_edits(&positions),
+            positions,
+            chars,
+            any,
+        };
+        if cache.states.len() >= self.automata.max_states {
+            return LazyState::Simulated(Arc::new(info));
+        }
+        let id = cache.states.len();
+        cache.ids.insert(info.positions.clone(), id);
+        cache.states.push(CachedState {
+            next: vec![None; info.chars.len() + 1],
+            info,
+        });
+        LazyState::Cached(id)
+    }
+}
+
+impl Walk for Searcher<'_> {
+    type State = LazyState;
+
+    fn start(&self) -> LazyState {
+        if let Some(start) = &self.cache.borrow().start {
+            return start.clone();
+        }
+        let start = self.intern(self.automata.nfa.start());
+        self.cache.borrow_mut().start = Some(start.clone());
+        start
+    }
+
+    fn step(&self, state: &LazyState, ch: char) -> LazyState {
+        let id = match state {
+            LazyState::Cached(id) => *id,
+            LazyState::Simulated(info) => {
+                return self.intern(self.automata.nfa.step(&info.positions, ch));
+            }
+        };
+        let (pos, positions) = {
+            let cache = self.cache.borrow();
+            let cached = &cache.states[id];
+            // All the characters without their own transitions lead to the same state.
+            let chars = &cached.info.chars;
+            let pos = chars.binary_search(&ch).unwrap_or(chars.len());
+            if let Some(next) = &cached.next[pos] {
+                return next.clone();
+            }
+            (pos, self.automata.nfa.step(&cached.info.positions, ch))
+        };
+        let next = self.intern(positions);
+        self.cache.borrow_mut().states[id].next[pos] = Some(next.clone());
+        next
+    }
+
+    fn is_final(&self, state: &LazyState) -> bool {
+        self.info(state, |info| info.final_edits.is_some())
+    }
+
+    fn is_dead(&self, state: &LazyState) -> bool {
+        self.info(state, |info| info.positions.is_empty())
+    }
+
+    fn find_next_edge(&self, state: &LazyState, ch: Option<char>) -> Option<char> {
+        let next = match ch {
+            Some(ch) => next_char(ch)?,
+            None => '\0',
+        };
+        self.info(state, |info| match info.any {
+            true => Some(next),
+            false => info
+                .chars
+                .get(info.chars.partition_point(|c| *c < next))
+                .copied(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod fuzzy_search {
+        use crate::{
+            automata::{all_strings, LazyLevenshteinAutomata, LevenshteinAutomata},
+            distance::levenshtein,
+        };
+
+        #[test]
+        fn test() {
+            let choices = all_strings(&['a', 'b', 'c'], 5);
+
+            for query in ["", "abc", "cabba"] {
+                for max_edits in 0..=4 {
+                    let expected = LevenshteinAutomata::new(query, max_edits)
+                        .fuzzy_search_with_edits(&choices);
+                    for max_states in [0, 3, usize::MAX] {
+                        let lazy = LazyLevenshteinAutomata::new(query, max_edits, max_states);
+                        assert_eq!(lazy.fuzzy_search_with_edits(&choices), expected);
+                        assert!(lazy.cached_states() <= max_states);
+                        // Searching again reuses the cache.
+                        let cached = lazy.cached_states();
+                        assert_eq!(lazy.fuzzy_search_with_edits(&choices), expected);
+                        assert_eq!(lazy.cached_states(), cached);
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn test_prefix() {
+            let choices =
+                ["fodder", "food", "foodstuffs", "foot", "fuddle", "good"].map(String::from);
+            let a = LazyLevenshteinAutomata::new_prefix("fuud", 2, 4);
+            assert_eq!(
+                a.fuzzy_search(&choices),
+                LevenshteinAutomata::new_prefix("fuud", 2).fuzzy_search(&choices)
+            );
+        }
+
+        #[test]
+        fn test_long() {
+            let query = "internationalization";
+            let choices = [
+                "interim",
+                "internalization",
+                "internationalisation",
+                "internationalization",
+                "internationally",
+            ]
+            .map(String::from);
+            let lazy = LazyLevenshteinAutomata::new(query, 4, 1000);
+            let expected: Vec<(String, usize)> = choices
+                .iter()
+                .map(|c| (c.clone(), levenshtein(query, c)))
+                .filter(|(_, distance)| *distance <= 4)
+                .collect();
+            assert_eq!(lazy.fuzzy_search_with_edits(choices.as_slice()), expected);
+            // Only the states along the few matching terms are built.
+            assert!(lazy.cached_states() < 1000);
+        }
+    }
+}
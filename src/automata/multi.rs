@@ -0,0 +1,132 @@
+use crate::automata::{walk::Walk, LevenshteinAutomata, SortedTerms};
+
+/// The union of the Levenshtein automata of several queries,
+/// so that sorted choices are walked once for all of them.
+#[derive(Debug)]
+pub struct MultiLevenshteinAutomata {
+    automata: Vec<LevenshteinAutomata>,
+}
+
+impl MultiLevenshteinAutomata {
+    /// Builds the automata for `(query, max_edits)` pairs.
+    pub fn new<'q, I: IntoIterator<Item = (&'q str, usize)>>(queries: I) -> Self {
+        Self {
+            automata: queries
+                .into_iter()
+                .map(|(query, max_edits)| LevenshteinAutomata::new(query, max_edits))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.automata.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.automata.is_empty()
+    }
+
+    /// Returns `(term, index of the query, distance)` for each query matching each term,
+    /// ordered by term and then by query.
+    pub fn fuzzy_search<T: SortedTerms + ?Sized>(
+        &self,
+        choices: &T,
+    ) -> Vec<(String, usize, usize)> {
+        self.search(choices)
+            .into_iter()
+            .flat_map(|(term, states)| {
+                states
+                    .into_iter()
+                    .enumerate()
+                    .filter_map(|(i, state)| Some((i, self.automata[i].dfa.final_edits(state)?)))
+                    .map(move |(i, edits)| (term.clone(), i, edits))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+}
+
+/// The product of the automata, i.e. the state of each one.
+impl Walk for MultiLevenshteinAutomata {
+    type State = Vec<u32>;
+
+    fn start(&self) -> Vec<u32> {
+        self.automata.iter().map(|a| a.dfa.start()).collect()
+    }
+
+    fn step(&self, state: &Vec<u32>, ch: char) -> Vec<u32> {
+        self.automata
+            .iter()
+            .zip(state)
+            .map(|(a, s)| a.dfa.step(s, ch))
+            .collect()
+    }
+
+    fn is_final(&self, state: &Vec<u32>) -> bool {
+        self.automata
+            .iter()
+            .zip(state)
+            .any(|(a, s)| a.dfa.is_final(s))
+    }
+
+    fn is_dead(&self, state: &Vec<u32>) -> bool {
+        self.automata
+            .iter()
+            .zip(state)
+            .all(|(a, s)| a.dfa.is_dead(s))
+    }
+
+    fn find_next_edge(&self, state: &Vec<u32>, ch: Option<char>) -> Option<char> {
+        self.automata
+            .iter()
+            .zip(state)
+            .filter(|(a, s)| !a.dfa.is_dead(s))
+            .filter_map(|(a, s)| a.dfa.find_next_edge(s, ch))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod fuzzy_search {
+        use crate::automata::{LevenshteinAutomata, MultiLevenshteinAutomata};
+
+        #[test]
+        fn test() {
+            let choices = ["flood", "fold", "food", "foods", "good", "mood", "wood"]
+                .map(String::from)
+                .to_vec();
+            let queries = [("fod", 1), ("good", 0), ("wool", 1), ("xyz", 1)];
+            let multi = MultiLevenshteinAutomata::new(queries);
+            assert_eq!(
+                multi.fuzzy_search(&choices),
+                vec![
+                    ("fold".into(), 0, 1),
+                    ("food".into(), 0, 1),
+                    ("good".into(), 1, 0),
+                    ("wood".into(), 2, 1),
+                ]
+            );
+
+            // Same as searching each query on its own.
+            for (i, (query, max_edits)) in queries.into_iter().enumerate() {
+                let expected =
+                    LevenshteinAutomata::new(query, max_edits).fuzzy_search_with_edits(&choices);
+                let actual: Vec<(String, usize)> = multi
+                    .fuzzy_search(&choices)
+                    .into_iter()
+                    .filter(|(_, query, _)| *query == i)
+                    .map(|(term, _, edits)| (term, edits))
+                    .collect();
+                assert_eq!(actual, expected);
+            }
+        }
+
+        #[test]
+        fn test_empty() {
+            let multi = MultiLevenshteinAutomata::new([]);
+            assert!(multi.is_empty());
+            assert_eq!(multi.fuzzy_search(&vec!["food".to_string()]), vec![]);
+        }
+    }
+}
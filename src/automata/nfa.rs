@@ -1,9 +1,15 @@
 use std::{
-    collections::{btree_set, hash_map, BTreeSet, HashMap, HashSet},
+    collections::{btree_set, hash_map, BTreeSet, HashMap},
+    fmt::Write,
     hash::Hash,
 };
 
-use crate::automata::dfa::{Dfa, Transitions as DfaTransitions};
+use anyhow::{ensure, Result};
+
+use crate::automata::{
+    dfa::{Dfa, Transitions as DfaTransitions},
+    dot::quote,
+};
 
 #[derive(Debug, Default, PartialEq, Eq)]
 struct StateIds {
@@ -16,28 +22,45 @@ impl StateIds {
         self.map.get(state).unwrap()
     }
 
-    fn insert(&mut self, state: &State) -> usize {
+    /// Returns the id of `state` and whether it was newly assigned.
+    fn insert(&mut self, state: &State) -> (usize, bool) {
         match self.map.entry(state.clone()) {
-            hash_map::Entry::Occupied(id) => *id.get(),
+            hash_map::Entry::Occupied(id) => (*id.get(), false),
             hash_map::Entry::Vacant(v) => {
                 v.insert(self.max_id);
                 self.max_id += 1;
-                self.max_id - 1
+                (self.max_id - 1, true)
             }
         }
     }
 
-    fn final_state_ids(&self, term_len: usize) -> HashSet<usize> {
+    /// Minimum number of edits among the positions of each state, indexed by id.
+    fn min_edits(&self) -> Vec<usize> {
+        let mut ret = vec![usize::MAX; self.max_id];
+        for (state, id) in self.map.iter() {
+            ret[*id] = state.min_edits().unwrap_or(usize::MAX);
+        }
+        ret
+    }
+
+    fn final_state_edits(&self, term_len: usize) -> HashMap<usize, usize> {
         self.map
             .iter()
-            .filter(|(state, _)| state.0.iter().any(|&s| s.0 == term_len))
-            .map(|(_, id)| *id)
+            .filter_map(|(state, id)| {
+                state
+                    .iter()
+                    .filter(|s| s.0 == term_len)
+                    .map(|s| s.1)
+                    .min()
+                    .map(|edits| (*id, edits))
+            })
             .collect()
     }
 }
 
+/// A set of NFA positions `(index, edits)`, i.e. a DFA state.
 #[derive(PartialEq, Eq, Hash, PartialOrd, Ord, Clone, Debug)]
-struct State(BTreeSet<(usize, usize)>);
+pub struct State(BTreeSet<(usize, usize)>);
 
 impl State {
     fn empty() -> Self {
@@ -56,10 +79,15 @@ impl State {
         self.0.extend(s.0)
     }
 
-    fn is_empty(&self) -> bool {
+    pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
+    /// Minimum number of edits among the positions.
+    pub fn min_edits(&self) -> Option<usize> {
+        self.iter().map(|s| s.1).min()
+    }
+
     fn reachable_destinations(&self, transitions: &Transitions, transition_type: &Type) -> Self {
         self.iter().fold(State::empty(), |mut agg, state| {
             let maybe = transitions.get(state).and_then(|destination_map| {
@@ -135,41 +163,206 @@ impl Transitions {
     }
 }
 
-pub struct Nfa<'q> {
-    query: &'q str,
+/// An element of a pattern, matching one character except for `Star`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Token {
+    Char(char),
+    /// `?`, any single character.
+    Any,
+    /// `*`, any sequence of characters, including the empty one.
+    Star,
+    /// `[abc]` or `[a-c]`, any of the characters.
+    Class(BTreeSet<char>),
+}
+
+/// An edge from one state of a pattern graph to another,
+/// labelled by the token it matches, or by nothing for an epsilon edge.
+pub type Edge = (usize, Option<Token>, usize);
+
+#[derive(Debug)]
+pub struct Nfa {
+    /// The state of the final positions, e.g. the number of tokens of a pattern.
+    end: usize,
     transitions: Transitions,
 }
 
-impl<'q> Nfa<'q> {
-    pub fn new(query: &'q str, max_edits: usize) -> Self {
+impl Nfa {
+    pub fn new(query: &str, max_edits: usize) -> Self {
+        let tokens = Vec::from_iter(query.chars().map(Token::Char));
+        Self::new_pattern(&tokens, max_edits)
+    }
+
+    /// Accepts any string within `max_edits` of a string matching `tokens`.
+    pub fn new_pattern(tokens: &[Token], max_edits: usize) -> Self {
+        let edges = Vec::from_iter(
+            tokens
+                .iter()
+                .enumerate()
+                .map(|(idx, token)| (idx, Some(token.clone()), idx + 1)),
+        );
+        Self::new_graph(&edges, tokens.len(), max_edits)
+    }
+
+    /// Accepts any string within `max_edits` of a string spelled by a path
+    /// from state 0 to `end` in the graph of `edges`, e.g. a Thompson construction.
+    pub fn new_graph(edges: &[Edge], end: usize, max_edits: usize) -> Self {
         let mut transitions = Transitions::default();
-        for (idx, ch) in query.chars().enumerate() {
+        let mut states = BTreeSet::from([end]);
+        for (from, token, to) in edges.iter() {
+            let (from, to) = (*from, *to);
+            states.extend([from, to]);
             for e in 0..max_edits + 1 {
-                // when the same character
-                transitions.add([(idx, e), (idx + 1, e)], Type::Input(ch));
+                match token {
+                    None => {
+                        transitions.add([(from, e), (to, e)], Type::Epsiron);
+                        continue;
+                    }
+                    // when the same character
+                    Some(Token::Char(ch)) => {
+                        transitions.add([(from, e), (to, e)], Type::Input(*ch))
+                    }
+                    Some(Token::Any) => transitions.add([(from, e), (to, e)], Type::Any),
+                    Some(Token::Class(chars)) => {
+                        for ch in chars.iter() {
+                            transitions.add([(from, e), (to, e)], Type::Input(*ch));
+                        }
+                    }
+                    // Any character can be consumed or the star skipped for free,
+                    // so edits around it are never needed.
+                    Some(Token::Star) => {
+                        transitions.add([(from, e), (from, e)], Type::Any);
+                        transitions.add([(from, e), (to, e)], Type::Epsiron);
+                        continue;
+                    }
+                }
                 if e < max_edits {
-                    // deletion
-                    transitions.add([(idx, e), (idx, e + 1)], Type::Any);
                     // insertion
-                    transitions.add([(idx, e), (idx + 1, e + 1)], Type::Epsiron);
+                    transitions.add([(from, e), (to, e + 1)], Type::Epsiron);
                     // substitution
-                    transitions.add([(idx, e), (idx + 1, e + 1)], Type::Any);
+                    transitions.add([(from, e), (to, e + 1)], Type::Any);
                 }
             }
+        }
+        for state in states {
             for e in 0..max_edits {
-                transitions.add(
-                    [(query.chars().count(), e), (query.chars().count(), e + 1)],
-                    Type::Any,
-                );
+                // deletion
+                transitions.add([(state, e), (state, e + 1)], Type::Any);
             }
         }
-        Self { query, transitions }
+        Self { end, transitions }
+    }
+
+    /// Accepts any string that has a prefix within `max_edits` of `query`.
+    pub fn new_prefix(query: &str, max_edits: usize) -> Self {
+        let mut nfa = Self::new(query, max_edits);
+        // Once the whole query is matched, the rest of the string is free.
+        for e in 0..max_edits + 1 {
+            nfa.transitions.add([(nfa.end, e), (nfa.end, e)], Type::Any);
+        }
+        nfa
+    }
+
+    /// Also accepts the strings preceded by anything, to find matches inside a text.
+    pub fn unanchored(mut self) -> Self {
+        self.transitions.add([(0, 0), (0, 0)], Type::Any);
+        self
     }
 
-    pub fn to_dfa(&self) -> Dfa {
+    /// Simulates the NFA without building the DFA: the positions before any input.
+    pub fn start(&self) -> State {
+        State::new(0, 0).epsilon_closure(&self.transitions)
+    }
+
+    /// The positions reached from `state` by reading `ch`.
+    pub fn step(&self, state: &State, ch: char) -> State {
+        state
+            .reachable_destinations(&self.transitions, &Type::Input(ch))
+            .epsilon_closure(&self.transitions)
+    }
+
+    /// The characters with their own transitions out of `state` in ascending order,
+    /// and whether any other character leads to a state that is not dead.
+    pub fn edges(&self, state: &State) -> (Vec<char>, bool) {
+        let mut chars = BTreeSet::new();
+        let mut any = false;
+        for typ in state
+            .iter()
+            .filter_map(|s| self.transitions.get_types(s))
+            .flatten()
+        {
+            match typ {
+                Type::Input(ch) => {
+                    chars.insert(*ch);
+                }
+                Type::Any => any = true,
+                Type::Epsiron => (),
+            }
+        }
+        (Vec::from_iter(chars), any)
+    }
+
+    /// The minimum number of edits among the final positions of `state`.
+    pub fn final_edits(&self, state: &State) -> Option<usize> {
+        state.iter().filter(|s| s.0 == self.end).map(|s| s.1).min()
+    }
+
+    /// Renders the NFA in the Graphviz DOT language, with `(index, edits)` states
+    /// and `Any`, `Epsilon` or `Input` edges; final states are double circles.
+    pub fn to_dot(&self) -> String {
+        let mut sources = Vec::from_iter(self.transitions.0.iter());
+        sources.sort_unstable_by_key(|(src, _)| **src);
+        let mut states = BTreeSet::from([(0, 0)]);
+        let mut edges = vec![];
+        for (src, destination_map) in sources {
+            let mut types = Vec::from_iter(destination_map.iter());
+            types.sort_unstable();
+            for (typ, dests) in types {
+                let label = match typ {
+                    Type::Epsiron => "Epsilon".to_string(),
+                    Type::Any => "Any".to_string(),
+                    Type::Input(ch) => format!("Input({:?})", ch),
+                };
+                for dest in dests {
+                    states.extend([*src, *dest]);
+                    edges.push((*src, *dest, quote(&label)));
+                }
+            }
+        }
+
+        let mut dot = String::from("digraph nfa {\n    rankdir=LR;\n    start [shape=point];\n");
+        for (index, edits) in states {
+            let shape = match index == self.end {
+                true => "doublecircle",
+                false => "circle",
+            };
+            writeln!(
+                dot,
+                "    \"{index},{edits}\" [label=\"({index}, {edits})\", shape={shape}];"
+            )
+            .unwrap();
+        }
+        dot.push_str("    start -> \"0,0\";\n");
+        for ((src_index, src_edits), (dest_index, dest_edits), label) in edges {
+            writeln!(
+                dot,
+                "    \"{src_index},{src_edits}\" -> \"{dest_index},{dest_edits}\" [label={label}];"
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Determinizes the NFA by subset construction,
+    /// failing once it reaches more than `max_states` states.
+    ///
+    /// Levenshtein automata have a number of states polynomial in the length of the query,
+    /// so they are built with `usize::MAX`, which never fails;
+    /// those of regexes and patterns can be exponential in their length.
+    pub fn to_dfa(&self, max_states: usize) -> Result<Dfa> {
         let mut state_ids = StateIds::default();
-        let start_state = State::new(0, 0).epsilon_closure(&self.transitions);
-        let start_id = state_ids.insert(&start_state);
+        let start_state = self.start();
+        let (start_id, _) = state_ids.insert(&start_state);
 
         let mut frontier = BTreeSet::<State>::from_iter([start_state]);
 
@@ -189,8 +382,13 @@ impl<'q> Nfa<'q> {
                 let next_state = current_state
                     .reachable_destinations(&self.transitions, typ)
                     .epsilon_closure(&self.transitions);
-                let next_id = state_ids.insert(&next_state);
-                if !next_state.is_empty() && state_ids.max_id - 1 == next_id {
+                let (next_id, is_new) = state_ids.insert(&next_state);
+                ensure!(
+                    state_ids.max_id <= max_states,
+                    "automaton has more than {} states",
+                    max_states
+                );
+                if !next_state.is_empty() && is_new {
                     frontier.insert(next_state);
                 }
                 let current_id = state_ids.get(&current_state);
@@ -206,12 +404,43 @@ impl<'q> Nfa<'q> {
             }
         }
 
-        Dfa {
+        let (dead_id, _) = state_ids.insert(&State::empty());
+
+        Ok(Dfa {
             start_id,
-            final_ids: state_ids.final_state_ids(self.query.len()),
-            sorted_chars: transitions.sorted_chars(),
+            dead_id,
+            min_edits: state_ids.min_edits(),
+            final_edits: state_ids.final_state_edits(self.end),
             transitions,
             any_transitions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod to_dot {
+        use crate::automata::nfa::Nfa;
+
+        #[test]
+        fn test() {
+            assert_eq!(
+                Nfa::new("a", 0).to_dot(),
+                r#"digraph nfa {
+    rankdir=LR;
+    start [shape=point];
+    "0,0" [label="(0, 0)", shape=circle];
+    "1,0" [label="(1, 0)", shape=doublecircle];
+    start -> "0,0";
+    "0,0" -> "1,0" [label="Input('a')"];
+}
+"#
+            );
+            let dot = Nfa::new("a", 1).to_dot();
+            assert!(dot.contains(r#""0,0" -> "1,1" [label="Epsilon"];"#));
+            assert!(dot.contains(r#""0,0" -> "1,1" [label="Any"];"#));
+            assert!(dot.contains(r#""0,0" -> "0,1" [label="Any"];"#));
+            assert!(dot.contains(r#""1,1" [label="(1, 1)", shape=doublecircle];"#));
         }
     }
 }
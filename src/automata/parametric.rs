@@ -0,0 +1,207 @@
+//! Parametric Levenshtein automata (Schulz and Mihov, 2002).
+//!
+//! The states of a Levenshtein automaton only depend on the query through
+//! the offset of their NFA positions and, for each input character,
+//! which characters of the query around that offset are equal to it
+//! (the *characteristic vector*).
+//! So the transitions can be computed once for each `max_edits`
+//! over positions relative to an offset, and a query automaton is
+//! instantiated from that table in time linear in the query length.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    sync::OnceLock,
+};
+
+use crate::automata::dfa::{Dfa, Transitions};
+
+/// NFA positions as `(offset, edits)`, with offsets relative to the smallest one.
+type MultiState = Vec<(usize, usize)>;
+
+pub struct ParametricDfa {
+    max_edits: usize,
+    /// Index 0 is the dead state, with no position left.
+    states: Vec<MultiState>,
+    /// `transitions[state * self.vector_count() + vector]` is the next state
+    /// and how far the offset moves.
+    transitions: Vec<(usize, usize)>,
+}
+
+/// The dead state and the start state `[(0, 0)]`.
+const DEAD: usize = 0;
+const START: usize = 1;
+
+impl ParametricDfa {
+    pub const MAX_EDITS: usize = 3;
+
+    /// The table for `max_edits`, computed on first use,
+    /// or `None` if `max_edits` is greater than [`ParametricDfa::MAX_EDITS`].
+    pub fn get(max_edits: usize) -> Option<&'static Self> {
+        static TABLES: [OnceLock<ParametricDfa>; ParametricDfa::MAX_EDITS + 1] =
+            [const { OnceLock::new() }; ParametricDfa::MAX_EDITS + 1];
+        TABLES
+            .get(max_edits)
+            .map(|table| table.get_or_init(|| Self::new(max_edits)))
+    }
+
+    /// Length of the characteristic vectors.
+    ///
+    /// After reading `t` characters, positions with at most `max_edits` edits
+    /// lie within `t ± max_edits` of the query,
+    /// so a position plus its look-ahead for deletions spans at most this many characters.
+    fn window(&self) -> usize {
+        2 * self.max_edits + 1
+    }
+
+    fn vector_count(&self) -> usize {
+        1 << self.window()
+    }
+
+    fn new(max_edits: usize) -> Self {
+        let mut table = Self {
+            max_edits,
+            states: vec![vec![], vec![(0, 0)]],
+            transitions: vec![],
+        };
+        let mut ids = HashMap::from([(vec![], DEAD), (vec![(0, 0)], START)]);
+
+        // States are numbered in the order they are found,
+        // so transitions are pushed in the order of their source state.
+        let mut current = 0;
+        while current < table.states.len() {
+            for vector in 0..table.vector_count() {
+                let (next, shift) = table.step(&table.states[current], vector);
+                let id = match ids.get(&next) {
+                    Some(id) => *id,
+                    None => {
+                        ids.insert(next.clone(), table.states.len());
+                        table.states.push(next);
+                        table.states.len() - 1
+                    }
+                };
+                table.transitions.push((id, shift));
+            }
+            current += 1;
+        }
+        table
+    }
+
+    /// Reads a character whose characteristic vector is `vector`,
+    /// i.e. bit `d` is set if the character equals the query character at `offset + d`.
+    fn step(&self, state: &MultiState, vector: usize) -> (MultiState, usize) {
+        let matches = |i: usize| i < self.window() && vector >> i & 1 == 1;
+        let mut next = BTreeSet::new();
+        for &(i, e) in state {
+            if matches(i) {
+                next.insert((i + 1, e));
+            }
+            if e < self.max_edits {
+                // insertion
+                next.insert((i, e + 1));
+                // substitution
+                next.insert((i + 1, e + 1));
+                // deletions followed by a match
+                for d in 1..=self.max_edits - e {
+                    if matches(i + d) {
+                        next.insert((i + d + 1, e + d));
+                    }
+                }
+            }
+        }
+        normalize(next)
+    }
+
+    /// Builds the automaton for `query`.
+    pub fn build_dfa(&self, query: &str) -> Dfa {
+        let query: Vec<char> = query.chars().collect();
+        let vector = |ch: char, offset: usize| {
+            (0..self.window())
+                .filter(|d| query.get(offset + d) == Some(&ch))
+                .fold(0, |agg, d| agg | 1 << d)
+        };
+
+        // Concrete states are pairs of a parametric state and an offset into the query.
+        let mut ids = HashMap::from([((DEAD, 0), 0), ((START, 0), 1)]);
+        let mut states = vec![(DEAD, 0), (START, 0)];
+        let mut transitions = Transitions::default();
+        let mut any_transitions = HashMap::new();
+
+        let mut current = 1;
+        while current < states.len() {
+            let (state, offset) = states[current];
+            let end = query.len().min(offset + self.window());
+            let chars = BTreeSet::from_iter(query.get(offset..end).unwrap_or_default());
+            // Characters not in the window all share the empty vector.
+            for ch in chars.into_iter().map(Some).chain([None]) {
+                let vector = ch.map_or(0, |ch| vector(*ch, offset));
+                let (next, shift) = self.transitions[state * self.vector_count() + vector];
+                // Like `Nfa::to_dfa`, leave transitions to the dead state implicit.
+                // A character in the window leads to a superset of the positions
+                // reached by any other character, so it is never dead on its own.
+                if next == DEAD {
+                    continue;
+                }
+                let key = (next, offset + shift);
+                let next_id = match ids.get(&key) {
+                    Some(id) => *id,
+                    None => {
+                        ids.insert(key, states.len());
+                        states.push(key);
+                        states.len() - 1
+                    }
+                };
+                match ch {
+                    Some(ch) => transitions.add([current, next_id], *ch),
+                    None => {
+                        any_transitions.insert(current, next_id);
+                    }
+                }
+            }
+            current += 1;
+        }
+
+        let positions = |(state, offset): (usize, usize)| {
+            self.states[state]
+                .iter()
+                .map(move |&(i, e)| (offset + i, e))
+        };
+        Dfa {
+            start_id: 1,
+            dead_id: 0,
+            min_edits: states
+                .iter()
+                .map(|s| positions(*s).map(|(_, e)| e).min().unwrap_or(usize::MAX))
+                .collect(),
+            // The rest of the query can be deleted from any position.
+            final_edits: (0..states.len())
+                .filter_map(|id| {
+                    positions(states[id])
+                        .map(|(i, e)| e + query.len().saturating_sub(i))
+                        .min()
+                        .filter(|edits| *edits <= self.max_edits)
+                        .map(|edits| (id, edits))
+                })
+                .collect(),
+            transitions,
+            any_transitions,
+        }
+    }
+}
+
+/// Drops the positions subsumed by another one, i.e. those that cannot
+/// lead to a match with fewer edits, and makes offsets relative.
+fn normalize(positions: BTreeSet<(usize, usize)>) -> (MultiState, usize) {
+    let subsumes = |(i, e): (usize, usize), (j, f): (usize, usize)| e < f && i.abs_diff(j) <= f - e;
+    let kept: Vec<(usize, usize)> = positions
+        .iter()
+        .filter(|&&p| !positions.iter().any(|&q| subsumes(q, p)))
+        .copied()
+        .collect();
+    match kept.iter().map(|(i, _)| *i).min() {
+        None => (vec![], 0),
+        Some(shift) => (
+            kept.into_iter().map(|(i, e)| (i - shift, e)).collect(),
+            shift,
+        ),
+    }
+}
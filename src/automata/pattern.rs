@@ -0,0 +1,203 @@
+use std::collections::BTreeSet;
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::automata::{
+    compact::CompactDfa,
+    nfa::{Nfa, Token},
+    walk::Walk,
+    SortedTerms,
+};
+
+/// Upper bound of the number of characters in a class,
+/// as each one gets its own transitions.
+const MAX_CLASS_LEN: usize = 1000;
+
+/// Upper bound of the number of DFA states built for a pattern or a regex,
+/// which can be exponential in their length.
+pub const MAX_DFA_STATES: usize = 10_000;
+
+/// A wildcard pattern matched with up to `max_edits` edits,
+/// where `?` matches any character, `*` any sequence of characters,
+/// `[abc]` or `[a-c]` any of the characters, and `\` escapes the next character.
+///
+/// The edits apply to the characters matched by `?`, `[...]` and literals,
+/// e.g. "col?r*" matches "colors" with no edit and "colour" with `max_edits = 1`.
+#[derive(Debug)]
+pub struct FuzzyPattern {
+    dfa: CompactDfa,
+}
+
+impl FuzzyPattern {
+    /// Fails if `pattern` has an unclosed `[`, an empty class,
+    /// a class of more than 1000 characters, or a trailing `\`,
+    /// or if its automaton would have more than 10,000 states.
+    pub fn new(pattern: &str, max_edits: usize) -> Result<Self> {
+        let tokens = parse(pattern)?;
+        let nfa = Nfa::new_pattern(&tokens, max_edits);
+        Ok(Self {
+            dfa: CompactDfa::new(&nfa.to_dfa(MAX_DFA_STATES)?),
+        })
+    }
+
+    /// `choices` must be sorted; see [`SortedChoices`](crate::automata::SortedChoices).
+    pub fn fuzzy_search(&self, choices: &[String]) -> Vec<String> {
+        self.fuzzy_search_sorted(choices)
+    }
+
+    pub fn fuzzy_search_sorted<T: SortedTerms + ?Sized>(&self, choices: &T) -> Vec<String> {
+        self.dfa
+            .search(choices)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Same as [`FuzzyPattern::fuzzy_search_sorted`], along with the number of edits
+    /// between each match and the closest string matching the pattern.
+    pub fn fuzzy_search_with_edits<T: SortedTerms + ?Sized>(
+        &self,
+        choices: &T,
+    ) -> Vec<(String, usize)> {
+        self.dfa
+            .search(choices)
+            .into_iter()
+            .map(|(term, state)| (term, self.dfa.final_edits(state).unwrap()))
+            .collect()
+    }
+}
+
+fn parse(pattern: &str) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut chars = pattern.chars();
+    while let Some(ch) = chars.next() {
+        let token = match ch {
+            '?' => Token::Any,
+            '*' => Token::Star,
+            '[' => Token::Class(parse_class(&mut chars)?),
+            '\\' => Token::Char(chars.next().context("trailing '\\' in pattern")?),
+            ch => Token::Char(ch),
+        };
+        // Consecutive stars are the same as one.
+        if token == Token::Star && tokens.last() == Some(&Token::Star) {
+            continue;
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Parses the rest of a class after its `[`.
+pub fn parse_class(chars: &mut impl Iterator<Item = char>) -> Result<BTreeSet<char>> {
+    let mut class = BTreeSet::new();
+    let mut prev = None;
+    loop {
+        match chars.next() {
+            None => bail!("unclosed '[' in pattern"),
+            Some(']') => break,
+            Some('-') if prev.is_some() => {
+                let start = prev.take().unwrap();
+                let end = match chars.next() {
+                    Some(']') | None => bail!("unfinished range in pattern"),
+                    Some('\\') => chars.next().context("trailing '\\' in pattern")?,
+                    Some(end) => end,
+                };
+                ensure!(start <= end, "invalid range {}-{} in pattern", start, end);
+                // Checked before expanding the range, which may span all of Unicode.
+                ensure!(
+                    (end as u32 - start as u32) < MAX_CLASS_LEN as u32,
+                    "class is longer than {} characters in pattern",
+                    MAX_CLASS_LEN
+                );
+                class.extend(start..=end);
+            }
+            Some(ch) => {
+                let ch = match ch {
+                    '\\' => chars.next().context("trailing '\\' in pattern")?,
+                    ch => ch,
+                };
+                class.insert(ch);
+                prev = Some(ch);
+            }
+        }
+    }
+    ensure!(!class.is_empty(), "empty class in pattern");
+    ensure!(
+        class.len() <= MAX_CLASS_LEN,
+        "class is longer than {} characters in pattern",
+        MAX_CLASS_LEN
+    );
+    Ok(class)
+}
+
+#[cfg(test)]
+mod tests {
+    mod new {
+        use crate::automata::FuzzyPattern;
+
+        #[test]
+        fn test() {
+            assert!(FuzzyPattern::new("col[ou", 1).is_err());
+            assert!(FuzzyPattern::new("col[]", 1).is_err());
+            assert!(FuzzyPattern::new("col[z-a]", 1).is_err());
+            assert!(FuzzyPattern::new("col\\", 1).is_err());
+            assert!(FuzzyPattern::new("col\\[*", 1).is_ok());
+        }
+
+        #[test]
+        fn test_large_class() {
+            assert!(FuzzyPattern::new("[\u{1}-\u{10FFFF}]", 1).is_err());
+            assert!(FuzzyPattern::new("[\u{1}-\u{3E8}\u{4E7}]", 1).is_err());
+            assert!(FuzzyPattern::new("[\u{1}-\u{3E8}]", 1).is_ok());
+        }
+    }
+
+    mod fuzzy_search {
+        use crate::automata::FuzzyPattern;
+
+        #[test]
+        fn test() {
+            let choices = [
+                "clr", "col", "color", "colour", "colours", "cool", "culler", "dolor",
+            ]
+            .map(String::from);
+            let p = FuzzyPattern::new("col?r*", 0).unwrap();
+            assert_eq!(p.fuzzy_search(&choices), vec!["color"]);
+            let p = FuzzyPattern::new("col*r*", 0).unwrap();
+            assert_eq!(p.fuzzy_search(&choices), vec!["color", "colour", "colours"]);
+            let p = FuzzyPattern::new("col?r*", 1).unwrap();
+            assert_eq!(
+                p.fuzzy_search_with_edits(choices.as_slice()),
+                vec![
+                    ("color".into(), 0),
+                    ("colour".into(), 1),
+                    ("colours".into(), 1),
+                    ("dolor".into(), 1),
+                ]
+            );
+            let p = FuzzyPattern::new("col?r*", 2).unwrap();
+            assert!(p.fuzzy_search(&choices).contains(&"clr".to_string()));
+        }
+
+        #[test]
+        fn test_class() {
+            let choices = ["bat", "cat", "cot", "hat", "mat", "rat"].map(String::from);
+            let p = FuzzyPattern::new("[bc-h]at", 0).unwrap();
+            assert_eq!(p.fuzzy_search(&choices), vec!["bat", "cat", "hat"]);
+            let p = FuzzyPattern::new("[bc]at", 1).unwrap();
+            assert_eq!(
+                p.fuzzy_search(&choices),
+                vec!["bat", "cat", "cot", "hat", "mat", "rat"]
+            );
+        }
+
+        #[test]
+        fn test_escape() {
+            let choices = ["a*", "a?", "ab"].map(String::from);
+            let p = FuzzyPattern::new("a\\*", 0).unwrap();
+            assert_eq!(p.fuzzy_search(&choices), vec!["a*"]);
+            let p = FuzzyPattern::new("a?", 0).unwrap();
+            assert_eq!(p.fuzzy_search(&choices), vec!["a*", "a?", "ab"]);
+        }
+    }
+}
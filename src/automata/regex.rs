@@ -0,0 +1,569 @@
+//! Approximate regular expressions, as in TRE or agrep:
+//! a string matches if it is within `max_edits` of a string matched by the regex.
+
+use std::{iter::Peekable, str::Chars};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::automata::{
+    compact::CompactDfa,
+    lazy::Searcher,
+    nfa::{Edge, Nfa, Token},
+    pattern::{parse_class, MAX_DFA_STATES},
+    walk::Walk,
+    LazyLevenshteinAutomata, SortedTerms,
+};
+
+/// Upper bound of `{m,n}` repetitions, which are expanded into copies.
+const MAX_REPEAT: usize = 1000;
+
+/// Upper bound of the length of the whole regex once repetitions are expanded,
+/// as nested repetitions multiply, e.g. `(a{1000}){1000}`.
+const MAX_EXPANDED_LEN: usize = MAX_REPEAT;
+
+/// A regex matched with up to `max_edits` edits,
+/// supporting literals, `.`, `[abc]` or `[a-c]` classes, groups, `|`,
+/// and the repetitions `*`, `+`, `?`, `{m}`, `{m,}` and `{m,n}`.
+/// `\` escapes the next character.
+///
+/// e.g. "colou?r" matches "color" and "colour" with no edit and "colr" with `max_edits = 1`.
+#[derive(Debug)]
+pub struct ApproximateRegex {
+    dfa: CompactDfa,
+    /// Also accepts the strings preceded by anything, to find the ends of matches in a text.
+    ///
+    /// Its DFA can be exponentially larger than `dfa`, e.g. for "a[ab]{17}",
+    /// so it is only built as far as texts lead it, as is `reversed`.
+    unanchored: LazyLevenshteinAutomata,
+    /// Accepts the reversed strings, to find the starts of matches in a text.
+    reversed: LazyLevenshteinAutomata,
+}
+
+/// A match in a text, as byte offsets.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextMatch {
+    pub start: usize,
+    pub end: usize,
+    pub edits: usize,
+}
+
+impl ApproximateRegex {
+    /// Fails if `regex` is malformed, uses unsupported syntax,
+    /// i.e. anchors and negated classes, or is too large:
+    /// classes and the regex with its repetitions expanded are limited to 1000 characters,
+    /// and its automaton to 10,000 states.
+    pub fn new(regex: &str, max_edits: usize) -> Result<Self> {
+        let ast = Parser::parse(regex)?;
+        let nfa = |ast: &Ast| {
+            let mut graph = Graph::default();
+            // The start and end states, with no edge into the start one.
+            let (start, end) = (graph.new_state(), graph.new_state());
+            graph.compile(ast, start, end);
+            Nfa::new_graph(&graph.edges, end, max_edits)
+        };
+        Ok(Self {
+            dfa: CompactDfa::new(&nfa(&ast).to_dfa(MAX_DFA_STATES)?),
+            unanchored: LazyLevenshteinAutomata::from_nfa(nfa(&ast).unanchored(), MAX_DFA_STATES),
+            reversed: LazyLevenshteinAutomata::from_nfa(nfa(&ast.reverse()), MAX_DFA_STATES),
+        })
+    }
+
+    /// `choices` must be sorted; see [`SortedChoices`](crate::automata::SortedChoices).
+    pub fn fuzzy_search(&self, choices: &[String]) -> Vec<String> {
+        self.fuzzy_search_sorted(choices)
+    }
+
+    pub fn fuzzy_search_sorted<T: SortedTerms + ?Sized>(&self, choices: &T) -> Vec<String> {
+        self.dfa
+            .search(choices)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Same as [`ApproximateRegex::fuzzy_search_sorted`], along with the number of edits
+    /// between each match and the closest string matched by the regex.
+    pub fn fuzzy_search_with_edits<T: SortedTerms + ?Sized>(
+        &self,
+        choices: &T,
+    ) -> Vec<(String, usize)> {
+        self.dfa
+            .search(choices)
+            .into_iter()
+            .map(|(term, state)| (term, self.dfa.final_edits(state).unwrap()))
+            .collect()
+    }
+
+    /// Number of edits between `text` and the closest string matched by the regex,
+    /// or `None` if it is more than `max_edits`.
+    pub fn distance(&self, text: &str) -> Option<usize> {
+        let state = text
+            .chars()
+            .fold(self.dfa.start(), |state, ch| self.dfa.step(&state, ch));
+        self.dfa.final_edits(state)
+    }
+
+    /// Finds the non-overlapping, non-empty matches inside `text`, from left to right.
+    ///
+    /// Each match ends where the earliest match not overlapping the previous ones ends,
+    /// extended or shortened to the fewest edits, and starts where it has the fewest edits.
+    ///
+    /// The automata it needs are built as the text is read and cached behind a lock,
+    /// so concurrent calls on the same regex run one at a time.
+    pub fn find_all(&self, text: &str) -> Vec<TextMatch> {
+        let (unanchored, reversed) = (self.unanchored.searcher(), self.reversed.searcher());
+        let mut ret = vec![];
+        let mut pos = 0;
+        while let Some(end) = find_end(&unanchored, text, pos) {
+            // Only the empty string matches before `end`, e.g. with "a?".
+            let Some(start) = find_start(&reversed, text, pos, end) else {
+                pos = end;
+                continue;
+            };
+            let (end, edits) = self.find_best_end(text, start);
+            ret.push(TextMatch { start, end, edits });
+            pos = end;
+        }
+        ret
+    }
+
+    /// The end with the fewest edits, then the rightmost one, of a match starting at `start`.
+    fn find_best_end(&self, text: &str, start: usize) -> (usize, usize) {
+        let mut best = (start, usize::MAX);
+        let mut state = self.dfa.start();
+        for (i, ch) in text[start..].char_indices() {
+            state = self.dfa.step(&state, ch);
+            if self.dfa.is_dead(&state) {
+                break;
+            }
+            if let Some(edits) = self.dfa.final_edits(state) {
+                if edits <= best.1 {
+                    best = (start + i + ch.len_utf8(), edits);
+                }
+            }
+        }
+        best
+    }
+}
+
+/// The earliest end of a match starting at `pos` or later.
+fn find_end(unanchored: &Searcher, text: &str, pos: usize) -> Option<usize> {
+    let mut state = unanchored.start();
+    for (i, ch) in text[pos..].char_indices() {
+        state = unanchored.step(&state, ch);
+        if unanchored.is_final(&state) {
+            return Some(pos + i + ch.len_utf8());
+        }
+    }
+    None
+}
+
+/// The start with the fewest edits, then the leftmost one,
+/// of a non-empty match ending at `end`.
+fn find_start(reversed: &Searcher, text: &str, pos: usize, end: usize) -> Option<usize> {
+    let mut best = None;
+    let mut state = reversed.start();
+    for (i, ch) in text[pos..end].char_indices().rev() {
+        state = reversed.step(&state, ch);
+        if reversed.is_dead(&state) {
+            break;
+        }
+        if let Some(edits) = reversed.final_edits(&state) {
+            if best.is_none_or(|(best_edits, _)| edits <= best_edits) {
+                best = Some((edits, pos + i));
+            }
+        }
+    }
+    best.map(|(_, start)| start)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Ast {
+    /// A single character, never `Token::Star`.
+    Token(Token),
+    Concat(Vec<Ast>),
+    Alternate(Vec<Ast>),
+    Repeat {
+        ast: Box<Ast>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+impl Ast {
+    /// Matches the reversed strings.
+    fn reverse(&self) -> Self {
+        match self {
+            Ast::Token(token) => Ast::Token(token.clone()),
+            Ast::Concat(items) => Ast::Concat(items.iter().rev().map(Ast::reverse).collect()),
+            Ast::Alternate(branches) => Ast::Alternate(branches.iter().map(Ast::reverse).collect()),
+            Ast::Repeat { ast, min, max } => Ast::Repeat {
+                ast: Box::new(ast.reverse()),
+                min: *min,
+                max: *max,
+            },
+        }
+    }
+
+    /// Number of edges `Graph::compile` makes for the tokens and empty strings,
+    /// saturating at `usize::MAX`.
+    fn expanded_len(&self) -> usize {
+        match self {
+            Ast::Token(_) => 1,
+            Ast::Concat(items) if items.is_empty() => 1,
+            Ast::Concat(items) | Ast::Alternate(items) => items
+                .iter()
+                .fold(0, |len, item| len.saturating_add(item.expanded_len())),
+            // A loop is compiled as one more copy.
+            Ast::Repeat { ast, min, max } => ast
+                .expanded_len()
+                .saturating_mul(max.unwrap_or(min.saturating_add(1))),
+        }
+    }
+}
+
+struct Parser<'r> {
+    chars: Peekable<Chars<'r>>,
+}
+
+impl Parser<'_> {
+    fn parse(regex: &str) -> Result<Ast> {
+        let mut parser = Parser {
+            chars: regex.chars().peekable(),
+        };
+        let ast = parser.parse_alternate()?;
+        ensure!(parser.chars.next().is_none(), "unmatched ')' in regex");
+        ensure!(
+            ast.expanded_len() <= MAX_EXPANDED_LEN,
+            "regex is longer than {} characters once repetitions are expanded",
+            MAX_EXPANDED_LEN
+        );
+        Ok(ast)
+    }
+
+    fn parse_alternate(&mut self) -> Result<Ast> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.chars.next_if_eq(&'|').is_some() {
+            branches.push(self.parse_concat()?);
+        }
+        match branches.len() {
+            1 => Ok(branches.pop().unwrap()),
+            _ => Ok(Ast::Alternate(branches)),
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<Ast> {
+        let mut items = vec![];
+        while let Some(ch) = self.chars.next_if(|ch| *ch != '|' && *ch != ')') {
+            let atom = self.parse_atom(ch)?;
+            items.push(self.parse_repeat(atom)?);
+        }
+        Ok(Ast::Concat(items))
+    }
+
+    fn parse_atom(&mut self, ch: char) -> Result<Ast> {
+        let token = match ch {
+            '(' => {
+                let ast = self.parse_alternate()?;
+                ensure!(self.chars.next() == Some(')'), "unclosed '(' in regex");
+                return Ok(ast);
+            }
+            '.' => Token::Any,
+            '[' => {
+                ensure!(
+                    self.chars.peek() != Some(&'^'),
+                    "negated classes are not supported in regex"
+                );
+                Token::Class(parse_class(&mut self.chars)?)
+            }
+            '\\' => Token::Char(self.chars.next().context("trailing '\\' in regex")?),
+            '*' | '+' | '?' | '{' => bail!("nothing to repeat before '{}' in regex", ch),
+            '^' | '$' => bail!("anchors are not supported in regex, escape '{}'", ch),
+            ch => Token::Char(ch),
+        };
+        Ok(Ast::Token(token))
+    }
+
+    fn parse_repeat(&mut self, mut ast: Ast) -> Result<Ast> {
+        loop {
+            let (min, max) = match self.chars.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => {
+                    self.chars.next();
+                    let min = self.parse_count()?.context("missing count in regex")?;
+                    let max = match self.chars.next() {
+                        Some('}') => Some(min),
+                        Some(',') => {
+                            let max = self.parse_count()?;
+                            ensure!(self.chars.next() == Some('}'), "unclosed '{{' in regex");
+                            max
+                        }
+                        _ => bail!("unclosed '{{' in regex"),
+                    };
+                    ensure!(
+                        max.is_none_or(|max| min <= max),
+                        "invalid repetition {{{},{}}} in regex",
+                        min,
+                        max.unwrap()
+                    );
+                    ast = Ast::Repeat {
+                        ast: Box::new(ast),
+                        min,
+                        max,
+                    };
+                    continue;
+                }
+                _ => return Ok(ast),
+            };
+            self.chars.next();
+            ast = Ast::Repeat {
+                ast: Box::new(ast),
+                min,
+                max,
+            };
+        }
+    }
+
+    fn parse_count(&mut self) -> Result<Option<usize>> {
+        let mut digits = String::new();
+        while let Some(ch) = self.chars.next_if(char::is_ascii_digit) {
+            digits.push(ch);
+        }
+        if digits.is_empty() {
+            return Ok(None);
+        }
+        let count: usize = digits.parse()?;
+        ensure!(
+            count <= MAX_REPEAT,
+            "repetition count {} is greater than {}",
+            count,
+            MAX_REPEAT
+        );
+        Ok(Some(count))
+    }
+}
+
+/// A Thompson construction of the regex.
+#[derive(Default)]
+struct Graph {
+    edges: Vec<Edge>,
+    state_count: usize,
+}
+
+impl Graph {
+    fn new_state(&mut self) -> usize {
+        self.state_count += 1;
+        self.state_count - 1
+    }
+
+    /// Adds the edges matching `ast` from `from` to `to`.
+    ///
+    /// Loops only go through new states, so that a fragment cannot be re-entered
+    /// through `from` or left through `to` from another fragment.
+    fn compile(&mut self, ast: &Ast, from: usize, to: usize) {
+        match ast {
+            Ast::Token(token) => self.edges.push((from, Some(token.clone()), to)),
+            Ast::Concat(items) if items.is_empty() => self.edges.push((from, None, to)),
+            Ast::Concat(items) => {
+                let mut current = from;
+                for (i, item) in items.iter().enumerate() {
+                    let next = match i + 1 == items.len() {
+                        true => to,
+                        false => self.new_state(),
+                    };
+                    self.compile(item, current, next);
+                    current = next;
+                }
+            }
+            Ast::Alternate(branches) => {
+                for branch in branches.iter() {
+                    let (start, end) = (self.new_state(), self.new_state());
+                    self.edges.push((from, None, start));
+                    self.compile(branch, start, end);
+                    self.edges.push((end, None, to));
+                }
+            }
+            Ast::Repeat { ast, min, max } => {
+                let mut current = from;
+                for _ in 0..*min {
+                    let next = self.new_state();
+                    self.compile(ast, current, next);
+                    current = next;
+                }
+                match max {
+                    None => {
+                        let (start, end) = (self.new_state(), self.new_state());
+                        self.edges.push((current, None, start));
+                        self.compile(ast, start, end);
+                        self.edges.push((end, None, start));
+                        self.edges.push((start, None, to));
+                    }
+                    Some(max) => {
+                        for _ in *min..*max {
+                            let next = self.new_state();
+                            self.edges.push((current, None, to));
+                            self.compile(ast, current, next);
+                            current = next;
+                        }
+                    }
+                }
+                self.edges.push((current, None, to));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod new {
+        use crate::automata::ApproximateRegex;
+
+        #[test]
+        fn test() {
+            for regex in [
+                "(ab", "ab)", "a**b{", "*a", "a{2,1}", "a{1001}", "^ab", "[^a]", "a\\",
+            ] {
+                assert!(ApproximateRegex::new(regex, 1).is_err(), "{}", regex);
+            }
+            assert!(ApproximateRegex::new("[\u{1}-\u{10FFFF}]", 1).is_err());
+            for regex in ["", "a|", "(a|b)*c{2,}d{1,3}", "a\\*\\^", "[a-c]+."] {
+                assert!(ApproximateRegex::new(regex, 1).is_ok(), "{}", regex);
+            }
+        }
+
+        #[test]
+        fn test_nested_repeat() {
+            for regex in [
+                "(a{1000}){1000}",
+                "((a{1000}){1000}){1000}",
+                "(a{100,}){10}",
+                "(a{60}b{60}){10}",
+            ] {
+                assert!(ApproximateRegex::new(regex, 1).is_err(), "{}", regex);
+            }
+            assert!(ApproximateRegex::new("(a{2}){3}", 1).is_ok());
+        }
+
+        #[test]
+        fn test_exponential_dfa() {
+            // The DFA finding matches anywhere in a text has 2^17 states.
+            let r = ApproximateRegex::new("a[ab]{17}", 0).unwrap();
+            let text = "ba".repeat(20);
+            assert_eq!(r.find_all(&text).len(), 2);
+            // An anchored one.
+            assert!(ApproximateRegex::new(".*a.{17}", 0).is_err());
+        }
+    }
+
+    mod distance {
+        use crate::automata::ApproximateRegex;
+
+        #[test]
+        fn test() {
+            let r = ApproximateRegex::new("colou?r", 1).unwrap();
+            assert_eq!(r.distance("color"), Some(0));
+            assert_eq!(r.distance("colour"), Some(0));
+            assert_eq!(r.distance("colr"), Some(1));
+            assert_eq!(r.distance("coulour"), Some(1));
+            assert_eq!(r.distance("clr"), None);
+
+            let r = ApproximateRegex::new("(ab|cd){2,3}", 1).unwrap();
+            assert_eq!(r.distance("abcd"), Some(0));
+            assert_eq!(r.distance("abcdab"), Some(0));
+            assert_eq!(r.distance("ab"), None);
+            assert_eq!(r.distance("abc"), Some(1));
+            assert_eq!(r.distance("abxdcd"), Some(1));
+            assert_eq!(r.distance("abcdabcd"), None);
+
+            let r = ApproximateRegex::new("a(b*|c+)d", 1).unwrap();
+            assert_eq!(r.distance("ad"), Some(0));
+            assert_eq!(r.distance("abbbd"), Some(0));
+            assert_eq!(r.distance("abcd"), Some(1));
+            assert_eq!(r.distance("abcbcd"), None);
+        }
+    }
+
+    mod fuzzy_search {
+        use crate::automata::ApproximateRegex;
+
+        #[test]
+        fn test() {
+            let choices =
+                ["clr", "color", "colour", "colours", "dolour", "flavor"].map(String::from);
+            let r = ApproximateRegex::new("colou?r", 0).unwrap();
+            assert_eq!(r.fuzzy_search(&choices), vec!["color", "colour"]);
+            let r = ApproximateRegex::new("(colou?|flavo)r", 1).unwrap();
+            assert_eq!(
+                r.fuzzy_search_with_edits(choices.as_slice()),
+                vec![
+                    ("color".into(), 0),
+                    ("colour".into(), 0),
+                    ("colours".into(), 1),
+                    ("dolour".into(), 1),
+                    ("flavor".into(), 0),
+                ]
+            );
+            let r = ApproximateRegex::new("co.*", 0).unwrap();
+            assert_eq!(r.fuzzy_search(&choices), vec!["color", "colour", "colours"]);
+        }
+    }
+
+    mod find_all {
+        use crate::automata::{ApproximateRegex, TextMatch};
+
+        #[test]
+        fn test_empty() {
+            let r = ApproximateRegex::new("a?", 0).unwrap();
+            let matches: Vec<(usize, usize)> = r
+                .find_all("xaya")
+                .iter()
+                .map(|m| (m.start, m.end))
+                .collect();
+            assert_eq!(matches, vec![(1, 2), (3, 4)]);
+        }
+
+        #[test]
+        fn test() {
+            let text = "my favourite colr is grey, or gray";
+            let r = ApproximateRegex::new("colou?r", 1).unwrap();
+            assert_eq!(
+                r.find_all(text),
+                vec![TextMatch {
+                    start: 13,
+                    end: 17,
+                    edits: 1
+                }]
+            );
+            let r = ApproximateRegex::new("gr[ae]y", 0).unwrap();
+            let matches: Vec<&str> = r
+                .find_all(text)
+                .iter()
+                .map(|m| &text[m.start..m.end])
+                .collect();
+            assert_eq!(matches, vec!["grey", "gray"]);
+            let r = ApproximateRegex::new("favou?rite", 1).unwrap();
+            assert_eq!(
+                r.find_all(text),
+                vec![TextMatch {
+                    start: 3,
+                    end: 12,
+                    edits: 0
+                }]
+            );
+        }
+
+        #[test]
+        fn test_unicode() {
+            let text = "un café crème";
+            let r = ApproximateRegex::new("cafe", 1).unwrap();
+            let matches: Vec<&str> = r
+                .find_all(text)
+                .iter()
+                .map(|m| &text[m.start..m.end])
+                .collect();
+            assert_eq!(matches, vec!["café"]);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    collections::{btree_map, btree_set, BTreeMap, BTreeSet},
+    iter,
+    ops::{Bound, Deref},
+    slice,
+};
+
+use anyhow::{bail, Result};
+
+/// A source of terms in ascending order that can seek to any position,
+/// which is all that automata search needs.
+pub trait SortedTerms {
+    type Iter<'a>: Iterator<Item = &'a str>
+    where
+        Self: 'a;
+
+    /// Iterates over the terms greater than or equal to `target`, in ascending order.
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a>;
+}
+
+/// The slice must be sorted; see [`SortedChoices`] for a checked alternative.
+impl SortedTerms for [String] {
+    type Iter<'a> = iter::Map<slice::Iter<'a, String>, fn(&String) -> &str>;
+
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a> {
+        let pos = self.partition_point(|term| term.as_str() < target);
+        self[pos..].iter().map(String::as_str)
+    }
+}
+
+impl SortedTerms for Vec<String> {
+    type Iter<'a> = <[String] as SortedTerms>::Iter<'a>;
+
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a> {
+        self.as_slice().seek(target)
+    }
+}
+
+impl SortedTerms for BTreeSet<String> {
+    type Iter<'a> = iter::Map<btree_set::Range<'a, String>, fn(&String) -> &str>;
+
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a> {
+        self.range::<str, _>((Bound::Included(target), Bound::Unbounded))
+            .map(String::as_str)
+    }
+}
+
+impl<V> SortedTerms for BTreeMap<String, V> {
+    type Iter<'a>
+        = iter::Map<btree_map::Range<'a, String, V>, fn((&'a String, &'a V)) -> &'a str>
+    where
+        V: 'a;
+
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a> {
+        self.range::<str, _>((Bound::Included(target), Bound::Unbounded))
+            .map(|(term, _)| term.as_str())
+    }
+}
+
+/// Choices that are checked to be sorted when constructed.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SortedChoices(Vec<String>);
+
+impl SortedChoices {
+    /// Fails if `choices` is not sorted in ascending order.
+    pub fn new(choices: Vec<String>) -> Result<Self> {
+        if let Some(pos) = choices.windows(2).position(|w| w[0] > w[1]) {
+            bail!(
+                "choices must be sorted, but {:?} comes before {:?}",
+                choices[pos],
+                choices[pos + 1]
+            );
+        }
+        Ok(Self(choices))
+    }
+
+    /// Sorts `choices` instead of rejecting them.
+    pub fn sorted(mut choices: Vec<String>) -> Self {
+        choices.sort();
+        Self(choices)
+    }
+
+    pub fn into_inner(self) -> Vec<String> {
+        self.0
+    }
+}
+
+impl Deref for SortedChoices {
+    type Target = [String];
+
+    fn deref(&self) -> &[String] {
+        &self.0
+    }
+}
+
+impl TryFrom<Vec<String>> for SortedChoices {
+    type Error = anyhow::Error;
+
+    fn try_from(choices: Vec<String>) -> Result<Self> {
+        Self::new(choices)
+    }
+}
+
+impl SortedTerms for SortedChoices {
+    type Iter<'a> = <[String] as SortedTerms>::Iter<'a>;
+
+    fn seek<'a>(&'a self, target: &str) -> Self::Iter<'a> {
+        self.0.seek(target)
+    }
+}
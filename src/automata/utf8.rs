@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+use crate::automata::{compact::CompactDfa, walk::Walk};
+
+/// A [`LevenshteinAutomata`](crate::automata::LevenshteinAutomata) compiled to run
+/// over the UTF-8 bytes of the input instead of its characters,
+/// e.g. to drive a byte trie or to search terms stored as bytes.
+///
+/// Each character transition is expanded into the byte sequences encoding it,
+/// through intermediate states that are shared where they lead to the same states,
+/// and every byte that cannot continue valid UTF-8 leads to the dead state,
+/// including surrogates and overlong encodings.
+#[derive(Debug)]
+pub struct Utf8Dfa {
+    start: u32,
+    dead: u32,
+    /// The target of each state for each byte.
+    transitions: Vec<[u32; 256]>,
+    /// The distance to the query of final states, `None` for the others.
+    final_edits: Vec<Option<usize>>,
+}
+
+impl Utf8Dfa {
+    pub fn new(dfa: &CompactDfa) -> Self {
+        let state_count = dfa.state_count();
+        let mut builder = Builder {
+            dfa,
+            // The states of `dfa` keep their ids, and intermediate ones come after them.
+            rows: vec![[dfa.dead; 256]; state_count],
+            ids: HashMap::new(),
+            tails: HashMap::new(),
+        };
+        for state in 0..state_count as u32 {
+            if state != dfa.dead {
+                builder.rows[state as usize] = builder.char_row(state);
+            }
+        }
+        let mut final_edits = Vec::from_iter((0..state_count as u32).map(|s| dfa.final_edits(s)));
+        final_edits.resize(builder.rows.len(), None);
+        Self {
+            start: dfa.start,
+            dead: dfa.dead,
+            transitions: builder.rows,
+            final_edits,
+        }
+    }
+
+    pub fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// The state reached from `state` by reading `byte`.
+    pub fn accept(&self, state: u32, byte: u8) -> u32 {
+        self.transitions[state as usize][byte as usize]
+    }
+
+    /// Whether the bytes read so far are accepted.
+    pub fn is_match(&self, state: u32) -> bool {
+        self.final_edits[state as usize].is_some()
+    }
+
+    /// Whether some continuation of the bytes read so far can still be accepted.
+    pub fn can_match(&self, state: u32) -> bool {
+        state != self.dead
+    }
+
+    /// Distance between the query and the input read to reach `state`,
+    /// or `None` if it does not match.
+    pub fn distance(&self, state: u32) -> Option<usize> {
+        self.final_edits[state as usize]
+    }
+
+    /// Number of states, including the intermediate ones within a character.
+    pub fn state_count(&self) -> usize {
+        self.transitions.len()
+    }
+
+    /// `choices` must be sorted by bytes, which for UTF-8 is the same as by characters.
+    pub fn fuzzy_search<'a, B: AsRef<[u8]>>(&self, choices: &'a [B]) -> Vec<&'a [u8]> {
+        self.fuzzy_search_with_edits(choices)
+            .into_iter()
+            .map(|(term, _)| term)
+            .collect()
+    }
+
+    /// Same as [`Utf8Dfa::fuzzy_search`], along with the distance between the query
+    /// and each match.
+    ///
+    /// All the terms sharing a prefix that leads to the dead state are skipped at once.
+    pub fn fuzzy_search_with_edits<'a, B: AsRef<[u8]>>(
+        &self,
+        choices: &'a [B],
+    ) -> Vec<(&'a [u8], usize)> {
+        let mut ret = vec![];
+        let mut pos = 0;
+        while let Some(term) = choices.get(pos) {
+            let term = term.as_ref();
+            let mut state = self.start;
+            let mut dead_at = None;
+            for (i, byte) in term.iter().enumerate() {
+                state = self.accept(state, *byte);
+                if !self.can_match(state) {
+                    dead_at = Some(i);
+                    break;
+                }
+            }
+            match dead_at {
+                Some(i) => {
+                    let prefix = &term[..=i];
+                    pos += choices[pos..].partition_point(|t| t.as_ref().starts_with(prefix));
+                }
+                None => {
+                    if let Some(edits) = self.distance(state) {
+                        ret.push((term, edits));
+                    }
+                    pos += 1;
+                }
+            }
+        }
+        ret
+    }
+}
+
+struct Builder<'a> {
+    dfa: &'a CompactDfa,
+    rows: Vec<[u32; 256]>,
+    /// The intermediate states by their transitions.
+    ids: HashMap<[u32; 256], u32>,
+    /// The intermediate states that read `remaining` continuation bytes,
+    /// the first one in `lo..=hi`, whatever they are, and then go to `target`.
+    tails: HashMap<(u8, u8, usize, u32), u32>,
+}
+
+impl Builder<'_> {
+    /// The transitions of a state of the character DFA.
+    fn char_row(&mut self, state: u32) -> [u32; 256] {
+        let mut row = [self.dfa.dead; 256];
+        for byte in 0..0x80u8 {
+            row[byte as usize] = self.dfa.step(&state, byte as char);
+        }
+        let mut buf = [0; 4];
+        let edges: Vec<(Vec<u8>, u32)> = self
+            .dfa
+            .edges(state)
+            .iter()
+            .filter(|(ch, _)| !ch.is_ascii())
+            .map(|(ch, target)| (ch.encode_utf8(&mut buf).as_bytes().to_vec(), *target))
+            .collect();
+        for lead in 0xC2..=0xF4u8 {
+            let edges = Vec::from_iter(edges.iter().filter(|(bytes, _)| bytes[0] == lead));
+            row[lead as usize] = self.node(state, &[lead], edges);
+        }
+        row
+    }
+
+    /// The intermediate state of `state` after reading `prefix`, the first bytes
+    /// of a character, where `edges` are the explicit transitions starting with `prefix`.
+    fn node(&mut self, state: u32, prefix: &[u8], edges: Vec<&(Vec<u8>, u32)>) -> u32 {
+        let (lo, hi) = match prefix {
+            [0xE0] => (0xA0, 0xBF),
+            [0xED] => (0x80, 0x9F),
+            [0xF0] => (0x90, 0xBF),
+            [0xF4] => (0x80, 0x8F),
+            _ => (0x80, 0xBF),
+        };
+        let remaining = sequence_len(prefix[0]) - prefix.len();
+        let default = self.dfa.default_target(state);
+        if edges.is_empty() {
+            return self.tail(lo, hi, remaining, default);
+        }
+        let mut row = [self.dfa.dead; 256];
+        for byte in lo..=hi {
+            let edges = Vec::from_iter(
+                edges
+                    .iter()
+                    .copied()
+                    .filter(|(bytes, _)| bytes[prefix.len()] == byte),
+            );
+            row[byte as usize] = match remaining {
+                1 => edges.first().map_or(default, |(_, target)| *target),
+                _ => self.node(state, &[prefix, &[byte]].concat(), edges),
+            };
+        }
+        self.intern(row)
+    }
+
+    fn tail(&mut self, lo: u8, hi: u8, remaining: usize, target: u32) -> u32 {
+        if let Some(id) = self.tails.get(&(lo, hi, remaining, target)) {
+            return *id;
+        }
+        let next = match remaining {
+            1 => target,
+            _ => self.tail(0x80, 0xBF, remaining - 1, target),
+        };
+        let mut row = [self.dfa.dead; 256];
+        row[lo as usize..=hi as usize].fill(next);
+        let id = self.intern(row);
+        self.tails.insert((lo, hi, remaining, target), id);
+        id
+    }
+
+    fn intern(&mut self, row: [u32; 256]) -> u32 {
+        let next_id = self.rows.len() as u32;
+        let id = *self.ids.entry(row).or_insert(next_id);
+        if id == next_id {
+            self.rows.push(row);
+        }
+        id
+    }
+}
+
+/// The number of bytes of a UTF-8 sequence starting with `lead`.
+fn sequence_len(lead: u8) -> usize {
+    match lead {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        _ => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod fuzzy_search {
+        use crate::{
+            automata::{all_strings, LevenshteinAutomata},
+            distance::levenshtein,
+        };
+
+        #[test]
+        fn test() {
+            let choices = all_strings(&['a', 'é', '€', '𝄞', '\u{D7FF}'], 3);
+
+            for query in ["", "a€", "é𝄞a"] {
+                for max_edits in 0..=2 {
+                    let a = LevenshteinAutomata::new(query, max_edits);
+                    let utf8 = a.to_utf8().unwrap();
+                    let expected: Vec<(&[u8], usize)> = choices
+                        .iter()
+                        .map(|c| (c.as_bytes(), levenshtein(query, c)))
+                        .filter(|(_, distance)| *distance <= max_edits)
+                        .collect();
+                    assert_eq!(utf8.fuzzy_search_with_edits(&choices), expected);
+                }
+            }
+        }
+
+        #[test]
+        fn test_bytes() {
+            let utf8 = LevenshteinAutomata::new("café", 1).to_utf8().unwrap();
+            let choices: [&[u8]; 5] = [
+                b"caf\xC3",
+                b"caf\xC3\xA9",
+                b"caf\xED\xA0\x80",
+                b"caf\xF0\x9F\x98\x80",
+                b"cafe",
+            ];
+            assert_eq!(
+                utf8.fuzzy_search_with_edits(&choices),
+                vec![(choices[1], 0), (choices[3], 1), (choices[4], 1),]
+            );
+        }
+    }
+
+    mod accept {
+        use crate::automata::{LevenshteinAutomata, Utf8Dfa};
+
+        fn run(utf8: &Utf8Dfa, bytes: &[u8]) -> u32 {
+            bytes
+                .iter()
+                .fold(utf8.start(), |state, byte| utf8.accept(state, *byte))
+        }
+
+        #[test]
+        fn test() {
+            let utf8 = LevenshteinAutomata::new("é", 0).to_utf8().unwrap();
+            assert!(utf8.is_match(run(&utf8, "é".as_bytes())));
+            assert!(!utf8.is_match(run(&utf8, b"\xC3")));
+            assert!(utf8.can_match(run(&utf8, b"\xC3")));
+            assert!(!utf8.can_match(run(&utf8, b"\xC3\xA8")));
+            // Overlong encoding of 'é'.
+            assert!(!utf8.can_match(run(&utf8, b"\xE0\x83\xA9")));
+
+            // Any character is one edit, whatever its encoded length.
+            let utf8 = LevenshteinAutomata::new("é", 1).to_utf8().unwrap();
+            for ch in ['a', 'è', '€', '\u{D7FF}', '\u{E000}', '𝄞', char::MAX] {
+                let state = run(&utf8, ch.to_string().as_bytes());
+                assert_eq!(utf8.distance(state), Some(1));
+            }
+            assert!(!utf8.can_match(run(&utf8, b"\xED\xA0\x80")));
+            assert!(!utf8.can_match(run(&utf8, b"\xF4\x90")));
+            assert!(!utf8.can_match(run(&utf8, b"\xFF")));
+
+            let a = LevenshteinAutomata::with_equivalence("é", 1, |ch| ch.to_ascii_lowercase());
+            assert!(a.to_utf8().is_err());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::automata::SortedTerms;
+
+/// A deterministic automaton whose accepted strings can be enumerated in order,
+/// which is how automata are intersected with sorted choices.
+pub trait Walk {
+    type State: Clone + Eq;
+
+    fn start(&self) -> Self::State;
+
+    fn step(&self, state: &Self::State, ch: char) -> Self::State;
+
+    fn is_final(&self, state: &Self::State) -> bool;
+
+    fn is_dead(&self, state: &Self::State) -> bool;
+
+    /// The smallest character greater than `ch` (or any character if `None`)
+    /// that may lead out of `state` to a state that is not dead.
+    fn find_next_edge(&self, state: &Self::State, ch: Option<char>) -> Option<char>;
+
+    /// Returns the smallest accepted string greater than or equal to `string`,
+    /// along with the final state it ends in.
+    ///
+    /// If the automaton loops, e.g. on `*` in a pattern, there may be no smallest one
+    /// (`"a\0b" > "a\0\0b" > ...`), so it stops at the first prefix that re-enters
+    /// a state of its own path and returns it with its state, which may not be final;
+    /// no string between `string` and that prefix is accepted either way.
+    fn next_valid_string(&self, string: String) -> Option<(String, Self::State)> {
+        // (length of the prefix in bytes, state after the prefix, last character tried)
+        let mut stack = vec![];
+        let mut state = self.start();
+
+        'label: {
+            for (i, ch) in string.char_indices() {
+                let next = self.step(&state, ch);
+                stack.push((i, state, Some(ch)));
+                state = next;
+                if self.is_dead(&state) {
+                    break 'label;
+                }
+            }
+            if self.is_final(&state) {
+                return Some((string, state));
+            }
+            stack.push((string.len(), state, None));
+        }
+
+        let mut path = string;
+        while let Some((len, state, ch)) = stack.pop() {
+            if let Some(ch) = self.find_next_edge(&state, ch) {
+                let next = self.step(&state, ch);
+                // Come back for the next character if nothing matches below this one.
+                stack.push((len, state, Some(ch)));
+                path.truncate(len);
+                path.push(ch);
+                if self.is_final(&next)
+                    || !self.is_dead(&next) && stack.iter().any(|(_, state, _)| *state == next)
+                {
+                    return Some((path, next));
+                }
+                stack.push((path.len(), next, None));
+            }
+        }
+        None
+    }
+
+    /// The terms of `choices` accepted by the automaton, in order,
+    /// along with the final state each one ends in.
+    fn search<T: SortedTerms + ?Sized>(&self, choices: &T) -> Vec<(String, Self::State)> {
+        let mut ret = vec![];
+        let mut maybe_string = self.next_valid_string(String::new());
+        while let Some((string, state)) = maybe_string {
+            match choices.seek(&string).next() {
+                Some(next) => {
+                    let mut next = next.to_string();
+                    if string == next {
+                        if self.is_final(&state) {
+                            ret.push((string, state));
+                        }
+                        next.push('\0');
+                    }
+                    maybe_string = self.next_valid_string(next);
+                }
+                _ => break,
+            }
+        }
+        ret
+    }
+
+    /// Same as [`Walk::search`], for an automaton over characters mapped by `fold`,
+    /// e.g. to lowercase.
+    ///
+    /// The accepted strings cannot be enumerated without knowing which characters
+    /// `fold` maps where, so this runs the automaton on the terms instead,
+    /// seeking past all the terms that share a prefix leading to the dead state.
+    fn search_folded<T: SortedTerms + ?Sized, F: Fn(char) -> char>(
+        &self,
+        choices: &T,
+        fold: F,
+    ) -> Vec<(String, Self::State)> {
+        let mut ret = vec![];
+        let mut target = String::new();
+        while let Some(term) = choices.seek(&target).next() {
+            let mut state = self.start();
+            let mut dead_at = None;
+            for (i, ch) in term.char_indices() {
+                state = self.step(&state, fold(ch));
+                if self.is_dead(&state) {
+                    dead_at = Some((i, ch));
+                    break;
+                }
+            }
+            target.clear();
+            target.push_str(term);
+            match dead_at.and_then(|(i, ch)| Some((i, next_char(ch)?))) {
+                Some((i, next)) => {
+                    target.truncate(i);
+                    target.push(next);
+                }
+                None => {
+                    if self.is_final(&state) {
+                        ret.push((term.to_string(), state));
+                    }
+                    target.push('\0');
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// The next Unicode scalar value after `ch`, skipping surrogates.
+pub fn next_char(ch: char) -> Option<char> {
+    match ch {
+        '\u{D7FF}' => Some('\u{E000}'),
+        char::MAX => None,
+        _ => char::from_u32(ch as u32 + 1),
+    }
+}
@@ -1,7 +1,29 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    cmp,
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    io::{Read, Write},
+    ops::RangeInclusive,
+};
+
+use anyhow::Result;
+
+use crate::suggestion::Suggestion;
+
+mod concurrent;
+mod format;
+mod mapped;
+mod nearest;
+mod par;
+mod stats;
+pub use concurrent::ConcurrentBkTree;
+pub use mapped::{MappedBkTree, MappedTreeLookup};
+pub use nearest::NearestLookup;
+pub use stats::{QueryStats, TreeStats};
 
 struct Node {
     term: String,
+    /// Number of times `term` was inserted.
+    count: usize,
     children: HashMap<usize, Node>,
 }
 
@@ -9,13 +31,84 @@ impl Node {
     fn new(term: String) -> Self {
         Self {
             term,
+            count: 1,
             children: HashMap::default(),
         }
     }
+
+    /// Inserts `choice` below this node with the given count
+    /// and returns whether it was a new term.
+    fn insert<E: Fn(&str, &str) -> usize>(
+        &mut self,
+        choice: String,
+        count: usize,
+        edit_distance: &E,
+    ) -> bool {
+        let mut cursor = self;
+        loop {
+            if cursor.term == choice {
+                cursor.count += count;
+                return false;
+            }
+            let dist = (edit_distance)(&cursor.term, &choice);
+            match cursor.children.entry(dist) {
+                Entry::Vacant(entry) => {
+                    let node = entry.insert(Node::new(choice));
+                    node.count = count;
+                    return true;
+                }
+                Entry::Occupied(entry) => {
+                    cursor = entry.into_mut();
+                }
+            }
+        }
+    }
+
+    /// Merges `other`, whose terms must all belong below this node,
+    /// and returns the number of new terms.
+    fn merge<E: Fn(&str, &str) -> usize>(&mut self, other: Node, edit_distance: &E) -> usize {
+        let mut new_terms = 0;
+        if self.term == other.term {
+            self.count += other.count;
+            // Every term under `other`'s child at `dist` is `dist` away from this term,
+            // so it belongs under this node's child at `dist`.
+            for (dist, child) in other.children {
+                match self.children.entry(dist) {
+                    Entry::Vacant(entry) => {
+                        new_terms += child.len();
+                        entry.insert(child);
+                    }
+                    Entry::Occupied(mut entry) => {
+                        new_terms += entry.get_mut().merge(child, edit_distance);
+                    }
+                }
+            }
+        } else {
+            new_terms += self.insert(other.term, other.count, edit_distance) as usize;
+            for child in other.children.into_values() {
+                new_terms += self.merge(child, edit_distance);
+            }
+        }
+        new_terms
+    }
+
+    /// Number of nodes in the subtree rooted at this node.
+    fn len(&self) -> usize {
+        let (mut len, mut stack) = (0, vec![self]);
+        while let Some(node) = stack.pop() {
+            len += 1;
+            stack.extend(node.children.values());
+        }
+        len
+    }
 }
 
+/// `BkTree<E>` is `Send` and `Sync` whenever `E` is.
+/// Queries only need `&self`, so a built tree can be shared across threads,
+/// e.g. in an `Arc`; to insert while other threads query, use [`ConcurrentBkTree`].
 pub struct BkTree<E: Fn(&str, &str) -> usize> {
     root: Option<Node>,
+    len: usize,
     edit_distance: E,
 }
 
@@ -23,7 +116,16 @@ pub struct TreeLookup<'q, E: Fn(&str, &str) -> usize> {
     choices: VecDeque<&'q Node>,
     edit_distance: &'q E,
     query: &'q str,
+    min_edits: usize,
     max_edits: usize,
+    stats: QueryStats,
+}
+
+impl<E: Fn(&str, &str) -> usize> TreeLookup<'_, E> {
+    /// Statistics of the traversal so far.
+    pub fn stats(&self) -> &QueryStats {
+        &self.stats
+    }
 }
 
 impl<'q, E: Fn(&str, &str) -> usize> Iterator for TreeLookup<'q, E> {
@@ -32,20 +134,31 @@ impl<'q, E: Fn(&str, &str) -> usize> Iterator for TreeLookup<'q, E> {
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(choice) = self.choices.pop_front() {
             let edits = (self.edit_distance)(&choice.term, self.query);
+            self.stats.nodes_visited += 1;
+            self.stats.distance_computations += 1;
 
             // Enqueue
+            // Every term under the child at `dist` is `dist` away from this node,
+            // so by the triangle inequality its distance to the query is
+            // within [|dist - edits|, dist + edits].
+            // That interval must overlap [min_edits, max_edits].
             let (lower, upper) = (
-                edits.saturating_sub(self.max_edits),
+                cmp::max(
+                    edits.saturating_sub(self.max_edits),
+                    self.min_edits.saturating_sub(edits),
+                ),
                 edits.saturating_add(self.max_edits),
             );
             for (dist, child) in choice.children.iter() {
                 if &lower <= dist && dist <= &upper {
                     self.choices.push_back(child);
+                } else {
+                    self.stats.nodes_pruned += 1;
                 }
             }
 
             // Return neighbor
-            if edits <= self.max_edits {
+            if self.min_edits <= edits && edits <= self.max_edits {
                 return Some(choice.term.to_string());
             }
         }
@@ -57,33 +170,118 @@ impl<E: Fn(&str, &str) -> usize> BkTree<E> {
     pub fn new(edit_distance: E) -> Self {
         Self {
             root: None,
+            len: 0,
             edit_distance,
         }
     }
 
+    /// Builds a tree by inserting `choices` in order.
+    pub fn from_iter_with<I: IntoIterator<Item = String>>(edit_distance: E, choices: I) -> Self {
+        let mut tree = Self::new(edit_distance);
+        tree.extend(choices);
+        tree
+    }
+
     pub fn insert(&mut self, choice: String) {
         match self.root {
             None => {
                 self.root = Some(Node::new(choice));
+                self.len += 1;
             }
             Some(ref mut root) => {
-                let mut cursor = root;
-                loop {
-                    if cursor.term == choice {
-                        break;
-                    }
-                    let dist = (self.edit_distance)(&cursor.term, &choice);
-                    if cursor.children.get(&dist).is_none() {
-                        cursor.children.insert(dist, Node::new(choice));
-                        break;
-                    }
-                    cursor = cursor.children.get_mut(&dist).unwrap();
+                if root.insert(choice, 1, &self.edit_distance) {
+                    self.len += 1;
                 }
             }
         }
     }
 
+    /// Moves all the terms of `other` into this tree, adding up the counts of shared terms.
+    ///
+    /// Where both trees have the same term at the same position,
+    /// the subtrees of `other` that have no counterpart in `self` are moved as a whole,
+    /// without computing any distance.
+    /// So merging is cheapest for trees built from partitions
+    /// that were all started with the same first term.
+    ///
+    /// The structure of `other` is reused as is, so it must have been built
+    /// with the same edit distance as `self`, not merely one of the same type.
+    pub fn merge(&mut self, other: BkTree<E>) {
+        let Some(other) = other.root else {
+            return;
+        };
+        match self.root {
+            None => {
+                self.len = other.len();
+                self.root = Some(other);
+            }
+            Some(ref mut root) => {
+                self.len += root.merge(other, &self.edit_distance);
+            }
+        }
+    }
+
+    /// Number of distinct terms in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns how many times `term` was inserted, or 0 if it is not in the tree.
+    pub fn count(&self, term: &str) -> usize {
+        let mut cursor = self.root.as_ref();
+        while let Some(node) = cursor {
+            if node.term == term {
+                return node.count;
+            }
+            let dist = (self.edit_distance)(&node.term, term);
+            cursor = node.children.get(&dist);
+        }
+        0
+    }
+
+    /// Writes the tree to `writer` in a versioned binary format.
+    ///
+    /// Only the structure and the terms are written, not `edit_distance`;
+    /// the same function must be supplied to [`BkTree::read_from`].
+    pub fn write_to<W: Write>(&self, writer: W) -> Result<()> {
+        format::write(self.root.as_ref(), writer)
+    }
+
+    /// Loads a tree written by [`BkTree::write_to`].
+    ///
+    /// Fails if the data has an unsupported format version or a bad checksum.
+    pub fn read_from<R: Read>(reader: R, edit_distance: E) -> Result<Self> {
+        let root = format::read(reader)?;
+        Ok(Self {
+            len: root.as_ref().map_or(0, Node::len),
+            root,
+            edit_distance,
+        })
+    }
+
     pub fn fuzzy_search<'q>(&'q self, query: &'q str, max_edits: usize) -> TreeLookup<'q, E> {
+        self.fuzzy_search_range(query, 0..=max_edits)
+    }
+
+    /// Finds the terms within `max_edits` of `query`,
+    /// ranked by distance and then by descending count.
+    pub fn fuzzy_search_ranked(&self, query: &str, max_edits: usize) -> Vec<Suggestion> {
+        let mut ret: Vec<Suggestion> = self.fuzzy_search_nearest(query, max_edits).collect();
+        ret.sort();
+        ret
+    }
+
+    /// Finds the terms whose distance to `query` lies within `edits`,
+    /// e.g. `2..=4` to skip near-exact matches.
+    pub fn fuzzy_search_range<'q>(
+        &'q self,
+        query: &'q str,
+        edits: RangeInclusive<usize>,
+    ) -> TreeLookup<'q, E> {
         TreeLookup {
             choices: match &self.root {
                 None => VecDeque::new(),
@@ -91,7 +289,17 @@ impl<E: Fn(&str, &str) -> usize> BkTree<E> {
             },
             edit_distance: &self.edit_distance,
             query,
-            max_edits,
+            min_edits: *edits.start(),
+            max_edits: *edits.end(),
+            stats: QueryStats::default(),
+        }
+    }
+}
+
+impl<E: Fn(&str, &str) -> usize> Extend<String> for BkTree<E> {
+    fn extend<I: IntoIterator<Item = String>>(&mut self, iter: I) {
+        for choice in iter {
+            self.insert(choice);
         }
     }
 }
@@ -114,5 +322,169 @@ mod tests {
                 );
             }
         }
+
+        mod count {
+            use crate::{bk::BkTree, distance::levenshtein, suggestion::Suggestion};
+
+            #[test]
+            fn test() {
+                let mut tree = BkTree::new(levenshtein);
+                for term in ["food", "fool", "food", "good", "fool", "food"] {
+                    tree.insert(term.into());
+                }
+                assert_eq!(tree.count("food"), 3);
+                assert_eq!(tree.count("fool"), 2);
+                assert_eq!(tree.count("mood"), 0);
+                assert_eq!(
+                    tree.fuzzy_search_ranked("foo", 1),
+                    vec![
+                        Suggestion {
+                            term: "food".into(),
+                            edits: 1,
+                            count: 3
+                        },
+                        Suggestion {
+                            term: "fool".into(),
+                            edits: 1,
+                            count: 2
+                        },
+                    ]
+                );
+            }
+        }
+
+        mod merge {
+            use crate::{bk::BkTree, distance::levenshtein};
+
+            fn build(choices: &[&str]) -> BkTree<fn(&str, &str) -> usize> {
+                BkTree::from_iter_with(levenshtein, choices.iter().map(|c| c.to_string()))
+            }
+
+            fn assert_same(lhs: &BkTree<fn(&str, &str) -> usize>, choices: &[&str]) {
+                let expected = build(choices);
+                assert_eq!(lhs.len(), expected.len());
+                for query in ["fod", "wod", "flod", "x"] {
+                    let mut l: Vec<String> = lhs.fuzzy_search(query, 2).collect();
+                    let mut r: Vec<String> = expected.fuzzy_search(query, 2).collect();
+                    l.sort();
+                    r.sort();
+                    assert_eq!(l, r);
+                }
+                for choice in choices {
+                    assert_eq!(lhs.count(choice), expected.count(choice));
+                }
+            }
+
+            #[test]
+            fn test_same_root() {
+                let lhs = ["food", "fool", "good", "flood"];
+                let rhs = ["food", "wood", "mood", "fool", "foods"];
+                let mut tree = build(&lhs);
+                tree.merge(build(&rhs));
+                assert_same(&tree, &[lhs.as_slice(), rhs.as_slice()].concat());
+            }
+
+            #[test]
+            fn test_different_root() {
+                let lhs = ["food", "fool", "good", "flood"];
+                let rhs = ["wood", "mood", "fool", "foods"];
+                let mut tree = BkTree::new(levenshtein as fn(&str, &str) -> usize);
+                tree.merge(build(&lhs));
+                tree.merge(build(&rhs));
+                assert_same(&tree, &[lhs.as_slice(), rhs.as_slice()].concat());
+            }
+        }
+
+        mod fuzzy_search_range {
+            use crate::{bk::BkTree, distance::levenshtein};
+
+            #[test]
+            fn test() {
+                let choices = [
+                    "food", "foods", "fool", "flood", "fold", "good", "wood", "mood",
+                ];
+                let mut tree = BkTree::new(levenshtein);
+                for term in choices {
+                    tree.insert(term.into());
+                }
+                for (min, max) in [(0, 0), (1, 1), (1, 2), (2, 4)] {
+                    let mut expected: Vec<&str> = choices
+                        .into_iter()
+                        .filter(|c| (min..=max).contains(&levenshtein("fod", c)))
+                        .collect();
+                    let mut actual: Vec<String> =
+                        tree.fuzzy_search_range("fod", min..=max).collect();
+                    expected.sort();
+                    actual.sort();
+                    assert_eq!(expected, actual);
+                }
+            }
+        }
+
+        mod write_to {
+            use crate::{bk::BkTree, distance::levenshtein};
+
+            fn tree() -> BkTree<fn(&str, &str) -> usize> {
+                let mut tree = BkTree::new(levenshtein as fn(&str, &str) -> usize);
+                for term in [
+                    "apple",
+                    "apply",
+                    "ape",
+                    "maple",
+                    "applet",
+                    "pineapple",
+                    "apple",
+                ] {
+                    tree.insert(term.into());
+                }
+                tree
+            }
+
+            #[test]
+            fn test() {
+                let tree = tree();
+                let mut bytes = vec![];
+                tree.write_to(&mut bytes).unwrap();
+
+                let loaded = BkTree::read_from(bytes.as_slice(), levenshtein).unwrap();
+                let mut expected: Vec<String> = tree.fuzzy_search("appel", 2).collect();
+                let mut actual: Vec<String> = loaded.fuzzy_search("appel", 2).collect();
+                expected.sort();
+                actual.sort();
+                assert_eq!(expected, actual);
+                assert_eq!(loaded.count("apple"), 2);
+            }
+
+            #[test]
+            fn test_empty() {
+                let mut bytes = vec![];
+                BkTree::new(levenshtein).write_to(&mut bytes).unwrap();
+                let loaded = BkTree::read_from(bytes.as_slice(), levenshtein).unwrap();
+                assert_eq!(loaded.fuzzy_search("apple", 2).count(), 0);
+            }
+
+            #[test]
+            fn test_version_mismatch() {
+                let mut bytes = vec![];
+                tree().write_to(&mut bytes).unwrap();
+                bytes[4] = 0xff;
+                let err = BkTree::read_from(bytes.as_slice(), levenshtein)
+                    .err()
+                    .unwrap();
+                assert!(err.to_string().contains("version"));
+            }
+
+            #[test]
+            fn test_checksum_mismatch() {
+                let mut bytes = vec![];
+                tree().write_to(&mut bytes).unwrap();
+                let last_term_byte = bytes.len() - 9;
+                bytes[last_term_byte] ^= 1;
+                let err = BkTree::read_from(bytes.as_slice(), levenshtein)
+                    .err()
+                    .unwrap();
+                assert!(err.to_string().contains("checksum"));
+            }
+        }
     }
 }
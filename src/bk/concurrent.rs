@@ -0,0 +1,220 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use arc_swap::ArcSwap;
+
+/// Same as `bk::Node`, but children are shared between snapshots.
+#[derive(Clone)]
+struct Node {
+    term: String,
+    count: usize,
+    children: HashMap<usize, Arc<Node>>,
+}
+
+impl Node {
+    fn new(term: String) -> Self {
+        Self {
+            term,
+            count: 1,
+            children: HashMap::default(),
+        }
+    }
+
+    /// Returns a copy of this node with `choice` inserted below it,
+    /// and whether `choice` was a new term.
+    ///
+    /// Only the nodes on the path to `choice` are copied;
+    /// all other subtrees are shared with `self`.
+    fn inserted<E: Fn(&str, &str) -> usize>(
+        &self,
+        choice: String,
+        edit_distance: &E,
+    ) -> (Self, bool) {
+        let mut node = self.clone();
+        if node.term == choice {
+            node.count += 1;
+            return (node, false);
+        }
+        let dist = (edit_distance)(&node.term, &choice);
+        let (child, is_new) = match node.children.get(&dist) {
+            None => (Node::new(choice), true),
+            Some(child) => child.inserted(choice, edit_distance),
+        };
+        node.children.insert(dist, Arc::new(child));
+        (node, is_new)
+    }
+}
+
+#[derive(Default)]
+struct Snapshot {
+    root: Option<Arc<Node>>,
+    len: usize,
+}
+
+/// A BK-tree that can be shared across threads and inserted into through `&self`.
+///
+/// Every insert publishes a new immutable snapshot of the tree,
+/// copying only the nodes on the path to the inserted term.
+/// Queries run against the snapshot current when they start,
+/// which they load without taking a lock,
+/// so they never wait for an insert and never observe a half-finished one.
+/// Inserts are serialized with each other.
+///
+/// A panic in `edit_distance` during an insert leaves the current snapshot untouched,
+/// and later inserts and queries carry on with it.
+///
+/// `ConcurrentBkTree<E>` is `Send` and `Sync` whenever `E` is.
+pub struct ConcurrentBkTree<E: Fn(&str, &str) -> usize> {
+    current: ArcSwap<Snapshot>,
+    writer: Mutex<()>,
+    edit_distance: E,
+}
+
+impl<E: Fn(&str, &str) -> usize> ConcurrentBkTree<E> {
+    pub fn new(edit_distance: E) -> Self {
+        Self {
+            current: ArcSwap::default(),
+            writer: Mutex::default(),
+            edit_distance,
+        }
+    }
+
+    fn snapshot(&self) -> Arc<Snapshot> {
+        self.current.load_full()
+    }
+
+    pub fn insert(&self, choice: String) {
+        // The snapshots are immutable, so a panic while holding the lock
+        // cannot leave them inconsistent.
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let snapshot = self.snapshot();
+        let (root, is_new) = match &snapshot.root {
+            None => (Node::new(choice), true),
+            Some(root) => root.inserted(choice, &self.edit_distance),
+        };
+        let next = Snapshot {
+            root: Some(Arc::new(root)),
+            len: snapshot.len + is_new as usize,
+        };
+        self.current.store(Arc::new(next));
+    }
+
+    /// Number of distinct terms in the tree.
+    pub fn len(&self) -> usize {
+        self.snapshot().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Same as [`BkTree::fuzzy_search`](crate::bk::BkTree::fuzzy_search),
+    /// run against the tree as of the start of the call.
+    pub fn fuzzy_search(&self, query: &str, max_edits: usize) -> Vec<String> {
+        let snapshot = self.snapshot();
+        let mut ret = vec![];
+        let mut choices = VecDeque::from_iter(snapshot.root.as_deref());
+        while let Some(choice) = choices.pop_front() {
+            let edits = (self.edit_distance)(&choice.term, query);
+
+            // Enqueue
+            let (lower, upper) = (
+                edits.saturating_sub(max_edits),
+                edits.saturating_add(max_edits),
+            );
+            for (dist, child) in choice.children.iter() {
+                if &lower <= dist && dist <= &upper {
+                    choices.push_back(child);
+                }
+            }
+
+            // Return neighbor
+            if edits <= max_edits {
+                ret.push(choice.term.to_string());
+            }
+        }
+        ret
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod insert {
+        use std::{sync::Arc, thread};
+
+        use crate::{
+            automata::LevenshteinAutomata,
+            bk::{BkTree, ConcurrentBkTree, MappedBkTree},
+            distance::levenshtein,
+            symspell::SymSpell,
+            vp::VpTree,
+        };
+
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        #[test]
+        fn test() {
+            assert_send_sync::<ConcurrentBkTree<fn(&str, &str) -> usize>>();
+            assert_send_sync::<BkTree<fn(&str, &str) -> usize>>();
+            assert_send_sync::<MappedBkTree<&[u8], fn(&str, &str) -> usize>>();
+            assert_send_sync::<SymSpell<fn(&str, &str) -> usize>>();
+            assert_send_sync::<VpTree<fn(&str, &str) -> f64>>();
+            assert_send_sync::<LevenshteinAutomata>();
+
+            let choices = [
+                "food", "foods", "fool", "flood", "fold", "good", "wood", "mood",
+            ];
+            let tree = Arc::new(ConcurrentBkTree::new(levenshtein));
+            let handles: Vec<_> = choices
+                .chunks(2)
+                .map(|chunk| {
+                    let tree = Arc::clone(&tree);
+                    let chunk = chunk.to_vec();
+                    thread::spawn(move || {
+                        for term in chunk {
+                            tree.insert(term.into());
+                            assert!(!tree.fuzzy_search(term, 0).is_empty());
+                        }
+                    })
+                })
+                .collect();
+            for handle in handles {
+                handle.join().unwrap();
+            }
+            tree.insert("food".into());
+
+            let mut expected = BkTree::new(levenshtein);
+            for term in choices {
+                expected.insert(term.into());
+            }
+            let mut expected: Vec<String> = expected.fuzzy_search("fod", 1).collect();
+            let mut actual = tree.fuzzy_search("fod", 1);
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual);
+            assert_eq!(tree.len(), choices.len());
+        }
+
+        #[test]
+        fn test_panic() {
+            let tree = Arc::new(ConcurrentBkTree::new(|lhs: &str, rhs: &str| {
+                assert!(lhs != "boom" && rhs != "boom");
+                levenshtein(lhs, rhs)
+            }));
+            tree.insert("food".into());
+            let panicking = Arc::clone(&tree);
+            assert!(thread::spawn(move || panicking.insert("boom".into()))
+                .join()
+                .is_err());
+
+            // The tree is still usable, as of before the panicking insert.
+            tree.insert("fool".into());
+            assert_eq!(tree.len(), 2);
+            let mut actual = tree.fuzzy_search("foo", 1);
+            actual.sort();
+            assert_eq!(actual, vec!["food", "fool"]);
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Binary layout of a persisted [`BkTree`](crate::bk::BkTree).
+//!
+//! All integers are little-endian `u64`.
+//!
+//! ```text
+//! header   : magic "FZBK" (4 bytes), version (u32), node_count, terms_len
+//! nodes    : node_count * [term_offset, term_len, count, distance, first_child, child_count]
+//! terms    : terms_len bytes of UTF-8, concatenated
+//! checksum : FNV-1a over everything above
+//! ```
+//!
+//! Nodes are laid out in breadth-first order starting from the root,
+//! so the children of a node are stored contiguously
+//! (sorted by their distance to the parent) at `first_child..first_child + child_count`.
+//! `count` is the number of times the term was inserted and
+//! `distance` is the edit distance between a node and its parent (0 for the root).
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+
+use crate::bk::Node;
+
+pub const MAGIC: [u8; 4] = *b"FZBK";
+pub const VERSION: u32 = 2;
+
+pub const HEADER_LEN: usize = 4 + 4 + 8 * 2;
+pub const NODE_LEN: usize = 8 * 6;
+pub const CHECKSUM_LEN: usize = 8;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Header {
+    pub node_count: u64,
+    pub terms_len: u64,
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() >= HEADER_LEN, "BK-tree data is truncated");
+        ensure!(bytes[..4] == MAGIC, "not a BK-tree file (bad magic)");
+        let version = u32::from_le_bytes(bytes[4..8].try_into().unwrap());
+        ensure!(
+            version == VERSION,
+            "unsupported BK-tree format version {} (expected {})",
+            version,
+            VERSION
+        );
+        Ok(Self {
+            node_count: read_u64(bytes, 8),
+            terms_len: read_u64(bytes, 16),
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut bytes = [0; HEADER_LEN];
+        bytes[..4].copy_from_slice(&MAGIC);
+        bytes[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.node_count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.terms_len.to_le_bytes());
+        bytes
+    }
+
+    /// Total length in bytes of the data described by this header,
+    /// or `None` if it does not fit in `usize`.
+    pub fn total_len(&self) -> Option<usize> {
+        usize::try_from(self.node_count)
+            .ok()?
+            .checked_mul(NODE_LEN)?
+            .checked_add(usize::try_from(self.terms_len).ok()?)?
+            .checked_add(HEADER_LEN + CHECKSUM_LEN)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct NodeRecord {
+    pub term_offset: u64,
+    pub term_len: u64,
+    pub count: u64,
+    pub distance: u64,
+    pub first_child: u64,
+    pub child_count: u64,
+}
+
+impl NodeRecord {
+    pub fn parse(bytes: &[u8]) -> Self {
+        Self {
+            term_offset: read_u64(bytes, 0),
+            term_len: read_u64(bytes, 8),
+            count: read_u64(bytes, 16),
+            distance: read_u64(bytes, 24),
+            first_child: read_u64(bytes, 32),
+            child_count: read_u64(bytes, 40),
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; NODE_LEN] {
+        let mut bytes = [0; NODE_LEN];
+        for (i, v) in [
+            self.term_offset,
+            self.term_len,
+            self.count,
+            self.distance,
+            self.first_child,
+            self.child_count,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            bytes[i * 8..(i + 1) * 8].copy_from_slice(&v.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Checks that the record refers to terms and children
+    /// inside the bounds given by the header.
+    pub fn validate(&self, index: u64, header: &Header) -> Result<()> {
+        ensure!(
+            self.term_offset
+                .checked_add(self.term_len)
+                .is_some_and(|end| end <= header.terms_len),
+            "term of node {} is out of bounds",
+            index
+        );
+        ensure!(
+            self.child_count == 0
+                || (self.first_child > index
+                    && self
+                        .first_child
+                        .checked_add(self.child_count)
+                        .is_some_and(|end| end <= header.node_count)),
+            "children of node {} are out of bounds",
+            index
+        );
+        Ok(())
+    }
+}
+
+pub fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// 64-bit FNV-1a.
+pub struct Checksum(u64);
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Checksum {
+    pub fn update(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+struct ChecksumWriter<W> {
+    inner: W,
+    checksum: Checksum,
+}
+
+impl<W: Write> Write for ChecksumWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.checksum.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+struct ChecksumReader<R> {
+    inner: R,
+    checksum: Checksum,
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.checksum.update(&buf[..n]);
+        Ok(n)
+    }
+}
+
+pub(super) fn write<W: Write>(root: Option<&Node>, writer: W) -> Result<()> {
+    // Breadth-first, so that children are assigned consecutive indices.
+    let mut nodes: Vec<(u64, &Node)> = vec![];
+    let mut queue = VecDeque::from_iter(root.map(|root| (0, root)));
+    while let Some((distance, node)) = queue.pop_front() {
+        nodes.push((distance, node));
+        let mut children = Vec::from_iter(node.children.iter());
+        children.sort_by_key(|(dist, _)| **dist);
+        queue.extend(
+            children
+                .into_iter()
+                .map(|(dist, child)| (*dist as u64, child)),
+        );
+    }
+
+    let header = Header {
+        node_count: nodes.len() as u64,
+        terms_len: nodes.iter().map(|(_, node)| node.term.len() as u64).sum(),
+    };
+
+    let mut writer = ChecksumWriter {
+        inner: writer,
+        checksum: Checksum::default(),
+    };
+    writer.write_all(&header.to_bytes())?;
+
+    let (mut term_offset, mut next_child) = (0, 1);
+    for (distance, node) in nodes.iter() {
+        let child_count = node.children.len() as u64;
+        let record = NodeRecord {
+            term_offset,
+            term_len: node.term.len() as u64,
+            count: node.count as u64,
+            distance: *distance,
+            first_child: if child_count == 0 { 0 } else { next_child },
+            child_count,
+        };
+        writer.write_all(&record.to_bytes())?;
+        term_offset += record.term_len;
+        next_child += child_count;
+    }
+    for (_, node) in nodes.iter() {
+        writer.write_all(node.term.as_bytes())?;
+    }
+
+    let checksum = writer.checksum.finish();
+    let mut writer = writer.inner;
+    writer.write_all(&checksum.to_le_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub(super) fn read<R: Read>(reader: R) -> Result<Option<Node>> {
+    let mut reader = ChecksumReader {
+        inner: reader,
+        checksum: Checksum::default(),
+    };
+
+    let mut bytes = [0; HEADER_LEN];
+    reader
+        .read_exact(&mut bytes)
+        .context("BK-tree data is truncated")?;
+    let header = Header::parse(&bytes)?;
+
+    let mut records = vec![];
+    let mut bytes = [0; NODE_LEN];
+    for index in 0..header.node_count {
+        reader
+            .read_exact(&mut bytes)
+            .context("BK-tree data is truncated")?;
+        let record = NodeRecord::parse(&bytes);
+        record.validate(index, &header)?;
+        records.push(record);
+    }
+
+    let mut terms = vec![];
+    (&mut reader)
+        .take(header.terms_len)
+        .read_to_end(&mut terms)?;
+    ensure!(
+        terms.len() as u64 == header.terms_len,
+        "BK-tree data is truncated"
+    );
+
+    let expected = reader.checksum.finish();
+    let mut bytes = [0; CHECKSUM_LEN];
+    reader
+        .inner
+        .read_exact(&mut bytes)
+        .context("BK-tree data is truncated")?;
+    if u64::from_le_bytes(bytes) != expected {
+        bail!("BK-tree checksum mismatch");
+    }
+
+    // Children always come after their parent,
+    // so building from the back lets each node take its finished children.
+    let mut nodes: Vec<Option<Node>> = Vec::with_capacity(records.len());
+    nodes.resize_with(records.len(), || None);
+    for (index, record) in records.iter().enumerate().rev() {
+        let start = record.term_offset as usize;
+        let term = std::str::from_utf8(&terms[start..start + record.term_len as usize])
+            .with_context(|| format!("term of node {} is not valid UTF-8", index))?;
+        let mut node = Node::new(term.to_string());
+        node.count = record.count as usize;
+        for child in record.first_child..record.first_child + record.child_count {
+            let distance = records[child as usize].distance as usize;
+            let child = nodes[child as usize]
+                .take()
+                .with_context(|| format!("node {} has more than one parent", child))?;
+            ensure!(
+                node.children.insert(distance, child).is_none(),
+                "node {} has two children at distance {}",
+                index,
+                distance
+            );
+        }
+        nodes[index] = Some(node);
+    }
+    Ok(nodes.into_iter().next().flatten())
+}
@@ -0,0 +1,349 @@
+use std::collections::VecDeque;
+
+use anyhow::{ensure, Result};
+
+use crate::bk::format::{Checksum, Header, NodeRecord, CHECKSUM_LEN, HEADER_LEN, NODE_LEN};
+
+/// A read-only BK-tree queried directly from the bytes written by
+/// [`BkTree::write_to`](crate::bk::BkTree::write_to).
+///
+/// Nothing is deserialized: `bytes` can be a memory-mapped file
+/// (anything implementing `AsRef<[u8]>`, e.g. `memmap2::Mmap`),
+/// so processes mapping the same file share its pages,
+/// and a query only pages in the nodes it visits.
+///
+/// Opening the data only checks its header and length. Each query checks the nodes
+/// it visits and panics on a malformed one, so call [`MappedBkTree::verify`] once
+/// on data that may be corrupt or crafted.
+///
+/// `MappedBkTree<B, E>` is `Send` and `Sync` whenever `B` and `E` are.
+pub struct MappedBkTree<B: AsRef<[u8]>, E: Fn(&str, &str) -> usize> {
+    bytes: B,
+    header: Header,
+    edit_distance: E,
+}
+
+pub struct MappedTreeLookup<'q, B: AsRef<[u8]>, E: Fn(&str, &str) -> usize> {
+    tree: &'q MappedBkTree<B, E>,
+    choices: VecDeque<usize>,
+    query: &'q str,
+    max_edits: usize,
+}
+
+impl<'q, B: AsRef<[u8]>, E: Fn(&str, &str) -> usize> Iterator for MappedTreeLookup<'q, B, E> {
+    type Item = &'q str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(choice) = self.choices.pop_front() {
+            let record = self.tree.record(choice);
+            let term = self.tree.term(choice, &record);
+            let edits = (self.tree.edit_distance)(term, self.query);
+
+            // Enqueue
+            // A node has at most one child per distance, so they are few enough
+            // to scan rather than relying on their order in the data.
+            // Their bounds were checked by `record`; the rest is checked once they are visited.
+            let (lower, upper) = (
+                edits.saturating_sub(self.max_edits) as u64,
+                edits.saturating_add(self.max_edits) as u64,
+            );
+            let first = record.first_child as usize;
+            let children = first..first + record.child_count as usize;
+            self.choices.extend(
+                children.filter(|i| (lower..=upper).contains(&self.tree.raw_record(*i).distance)),
+            );
+
+            // Return neighbor
+            if edits <= self.max_edits {
+                return Some(term);
+            }
+        }
+        None
+    }
+}
+
+impl<B: AsRef<[u8]>, E: Fn(&str, &str) -> usize> MappedBkTree<B, E> {
+    /// Checks the header of `bytes` (format version and lengths) and wraps it,
+    /// without reading the nodes; see [`MappedBkTree::verify`].
+    ///
+    /// `edit_distance` must be the same function the tree was built with.
+    pub fn new(bytes: B, edit_distance: E) -> Result<Self> {
+        let data = bytes.as_ref();
+        let header = Header::parse(data)?;
+        ensure!(
+            header.total_len() == Some(data.len()),
+            "BK-tree data has an unexpected length"
+        );
+        Ok(Self {
+            bytes,
+            header,
+            edit_distance,
+        })
+    }
+
+    /// Checks the whole data: the checksum, the bounds and terms of every node,
+    /// and that the nodes form a tree, as [`BkTree::read_from`](crate::bk::BkTree::read_from) does.
+    ///
+    /// This reads every page, so it is meant to be run once on untrusted data,
+    /// rather than on every open.
+    pub fn verify(&self) -> Result<()> {
+        let data = self.bytes.as_ref();
+        let body_len = data.len() - CHECKSUM_LEN;
+        let mut checksum = Checksum::default();
+        checksum.update(&data[..body_len]);
+        ensure!(
+            checksum.finish().to_le_bytes() == data[body_len..],
+            "BK-tree checksum mismatch"
+        );
+
+        // Nodes are written breadth-first, so the children of each node
+        // come right after those of the previous nodes: every node but the root
+        // then has exactly one parent.
+        let mut next_child = 1;
+        for index in 0..self.node_count() {
+            let record = self.checked_record(index)?;
+            if record.child_count == 0 {
+                continue;
+            }
+            ensure!(
+                record.first_child == next_child,
+                "children of node {} overlap or skip other nodes",
+                index
+            );
+            next_child += record.child_count;
+
+            let first = record.first_child as usize;
+            let mut distances: Vec<u64> = (first..first + record.child_count as usize)
+                .map(|child| self.raw_record(child).distance)
+                .collect();
+            distances.sort_unstable();
+            ensure!(
+                distances.windows(2).all(|w| w[0] != w[1]),
+                "node {} has two children at the same distance",
+                index
+            );
+        }
+        ensure!(
+            self.node_count() == 0 || next_child == self.header.node_count,
+            "BK-tree has nodes without a parent"
+        );
+        Ok(())
+    }
+
+    /// The record of node `index`, without checking what it refers to.
+    fn raw_record(&self, index: usize) -> NodeRecord {
+        let start = HEADER_LEN + index * NODE_LEN;
+        NodeRecord::parse(&self.bytes.as_ref()[start..start + NODE_LEN])
+    }
+
+    /// The record of node `index`, after checking its bounds and term.
+    fn checked_record(&self, index: usize) -> Result<NodeRecord> {
+        let record = self.raw_record(index);
+        record.validate(index as u64, &self.header)?;
+        ensure!(
+            std::str::from_utf8(self.term_bytes(&record)).is_ok(),
+            "term of node {} is not valid UTF-8",
+            index
+        );
+        Ok(record)
+    }
+
+    fn node_count(&self) -> usize {
+        self.header.node_count as usize
+    }
+
+    /// The record of node `index`, after checking its bounds but not its term;
+    /// see [`MappedBkTree::term`].
+    fn record(&self, index: usize) -> NodeRecord {
+        let record = self.raw_record(index);
+        if let Err(e) = record.validate(index as u64, &self.header) {
+            panic!("{:#}; use MappedBkTree::verify on untrusted data", e);
+        }
+        record
+    }
+
+    fn term_bytes(&self, record: &NodeRecord) -> &[u8] {
+        let start = HEADER_LEN + self.node_count() * NODE_LEN + record.term_offset as usize;
+        let end = start + record.term_len as usize;
+        &self.bytes.as_ref()[start..end]
+    }
+
+    /// The term of node `index`, whose `record` was checked with [`MappedBkTree::record`].
+    fn term(&self, index: usize, record: &NodeRecord) -> &str {
+        match std::str::from_utf8(self.term_bytes(record)) {
+            Ok(term) => term,
+            Err(_) => panic!(
+                "term of node {} is not valid UTF-8; use MappedBkTree::verify on untrusted data",
+                index
+            ),
+        }
+    }
+
+    pub fn fuzzy_search<'q>(
+        &'q self,
+        query: &'q str,
+        max_edits: usize,
+    ) -> MappedTreeLookup<'q, B, E> {
+        MappedTreeLookup {
+            tree: self,
+            choices: match self.node_count() {
+                0 => VecDeque::new(),
+                _ => VecDeque::from(vec![0]),
+            },
+            query,
+            max_edits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod fuzzy_search {
+        use crate::{
+            bk::{
+                format::{NodeRecord, HEADER_LEN, NODE_LEN},
+                BkTree, MappedBkTree,
+            },
+            distance::levenshtein,
+        };
+
+        #[test]
+        fn test() {
+            let mut tree = BkTree::new(levenshtein);
+            for term in ["apple", "apply", "ape", "maple", "applet", "pineapple"] {
+                tree.insert(term.into());
+            }
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            let mut expected: Vec<String> = tree.fuzzy_search("appel", 2).collect();
+            let mut actual: Vec<&str> = mapped.fuzzy_search("appel", 2).collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual);
+        }
+
+        #[test]
+        fn test_truncated() {
+            let mut tree = BkTree::new(levenshtein);
+            tree.insert("apple".into());
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            assert!(MappedBkTree::new(&bytes[..bytes.len() - 1], levenshtein).is_err());
+        }
+
+        #[test]
+        fn test_unsorted_children() {
+            let mut tree = BkTree::new(levenshtein);
+            for term in ["apple", "apply", "maple", "pineapple"] {
+                tree.insert(term.into());
+            }
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+
+            // Reverse the children of the root, which are sorted by distance when written.
+            let root = NodeRecord::parse(&bytes[HEADER_LEN..]);
+            let (first, count) = (root.first_child as usize, root.child_count as usize);
+            assert!(count > 1);
+            let records = HEADER_LEN + first * NODE_LEN..HEADER_LEN + (first + count) * NODE_LEN;
+            let mut children: Vec<Vec<u8>> = bytes[records.clone()]
+                .chunks(NODE_LEN)
+                .map(Vec::from)
+                .collect();
+            children.reverse();
+            bytes.splice(records, children.concat());
+
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            let mut actual: Vec<&str> = mapped.fuzzy_search("apple", 1).collect();
+            actual.sort();
+            assert_eq!(actual, vec!["apple", "apply"]);
+        }
+    }
+
+    mod verify {
+        use crate::{
+            bk::{
+                format::{Checksum, MAGIC, VERSION},
+                BkTree, MappedBkTree,
+            },
+            distance::levenshtein,
+        };
+
+        /// Writes nodes whose records are `[term_offset, term_len, count, distance,
+        /// first_child, child_count]`, with a valid checksum.
+        fn write(records: &[[u64; 6]], terms: &str) -> Vec<u8> {
+            let mut bytes = Vec::from(MAGIC);
+            bytes.extend(VERSION.to_le_bytes());
+            bytes.extend((records.len() as u64).to_le_bytes());
+            bytes.extend((terms.len() as u64).to_le_bytes());
+            for record in records {
+                bytes.extend(record.iter().flat_map(|v| v.to_le_bytes()));
+            }
+            bytes.extend(terms.as_bytes());
+            let mut checksum = Checksum::default();
+            checksum.update(&bytes);
+            bytes.extend(checksum.finish().to_le_bytes());
+            bytes
+        }
+
+        #[test]
+        fn test() {
+            let mut tree = BkTree::new(levenshtein);
+            for term in ["apple", "apply", "ape"] {
+                tree.insert(term.into());
+            }
+            let mut bytes = vec![];
+            tree.write_to(&mut bytes).unwrap();
+            assert!(MappedBkTree::new(bytes.as_slice(), levenshtein)
+                .unwrap()
+                .verify()
+                .is_ok());
+
+            // A flipped term byte is only found by `verify`, not by `new`.
+            let len = bytes.len();
+            bytes[len - 9] ^= 0xFF;
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            assert!(mapped.verify().is_err());
+        }
+
+        #[test]
+        fn test_shared_children() {
+            // Node i has children i + 1 and i + 2, so the later nodes have two parents
+            // and a query visits them exponentially many times.
+            let records: Vec<[u64; 6]> = (0..30)
+                .map(|i| {
+                    [
+                        0,
+                        1,
+                        1,
+                        (i > 0) as u64 + (i % 2 == 0) as u64,
+                        i + 1,
+                        2.min(29 - i),
+                    ]
+                })
+                .collect();
+            let bytes = write(&records, "a");
+            assert!(BkTree::read_from(bytes.as_slice(), levenshtein).is_err());
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            assert!(mapped.verify().is_err());
+
+            // The same children at the same distance.
+            let bytes = write(
+                &[[0, 1, 1, 0, 1, 2], [0, 1, 1, 1, 0, 0], [0, 1, 1, 1, 0, 0]],
+                "a",
+            );
+            assert!(BkTree::read_from(bytes.as_slice(), levenshtein).is_err());
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            assert!(mapped.verify().is_err());
+
+            let bytes = write(
+                &[[0, 1, 1, 0, 1, 2], [0, 1, 1, 1, 0, 0], [0, 1, 1, 2, 0, 0]],
+                "a",
+            );
+            assert!(BkTree::read_from(bytes.as_slice(), levenshtein).is_ok());
+            let mapped = MappedBkTree::new(bytes.as_slice(), levenshtein).unwrap();
+            assert!(mapped.verify().is_ok());
+        }
+    }
+}
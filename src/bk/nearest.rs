@@ -0,0 +1,146 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::{
+    bk::{BkTree, Node},
+    suggestion::Suggestion,
+};
+
+enum Item<'q> {
+    /// A node whose distance to the query is not computed yet.
+    Node(&'q Node),
+    /// A node whose distance to the query is known to be within `max_edits`.
+    Match(&'q Node),
+}
+
+/// An entry of the priority queue;
+/// `bound` is a lower bound of the distance to the query
+/// of every term reachable from `item`.
+struct Candidate<'q> {
+    bound: usize,
+    item: Item<'q>,
+}
+
+impl Candidate<'_> {
+    fn is_match(&self) -> bool {
+        matches!(self.item, Item::Match(_))
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    // `BinaryHeap` is a max-heap, so smaller bounds must compare greater.
+    // On ties, matches come first so that they are yielded as early as possible.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .bound
+            .cmp(&self.bound)
+            .then_with(|| self.is_match().cmp(&other.is_match()))
+    }
+}
+
+/// Yields matches in non-decreasing order of distance.
+pub struct NearestLookup<'q, E: Fn(&str, &str) -> usize> {
+    candidates: BinaryHeap<Candidate<'q>>,
+    edit_distance: &'q E,
+    query: &'q str,
+    max_edits: usize,
+}
+
+impl<'q, E: Fn(&str, &str) -> usize> Iterator for NearestLookup<'q, E> {
+    type Item = Suggestion;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(Candidate { bound, item }) = self.candidates.pop() {
+            let choice = match item {
+                // No remaining candidate can be closer than `bound`.
+                Item::Match(choice) => {
+                    return Some(Suggestion {
+                        term: choice.term.to_string(),
+                        edits: bound,
+                        count: choice.count,
+                    })
+                }
+                Item::Node(choice) => choice,
+            };
+            let edits = (self.edit_distance)(&choice.term, self.query);
+
+            // Enqueue
+            for (dist, child) in choice.children.iter() {
+                let bound = dist.abs_diff(edits);
+                if bound <= self.max_edits {
+                    self.candidates.push(Candidate {
+                        bound,
+                        item: Item::Node(child),
+                    });
+                }
+            }
+
+            if edits <= self.max_edits {
+                self.candidates.push(Candidate {
+                    bound: edits,
+                    item: Item::Match(choice),
+                });
+            }
+        }
+        None
+    }
+}
+
+impl<E: Fn(&str, &str) -> usize> BkTree<E> {
+    /// Same as [`BkTree::fuzzy_search`], but traverses the tree best-first
+    /// so that matches arrive nearest first, e.g. `.take(n)` only explores
+    /// as much of the tree as needed to find the `n` closest terms.
+    pub fn fuzzy_search_nearest<'q>(
+        &'q self,
+        query: &'q str,
+        max_edits: usize,
+    ) -> NearestLookup<'q, E> {
+        NearestLookup {
+            candidates: match &self.root {
+                None => BinaryHeap::new(),
+                Some(root) => BinaryHeap::from(vec![Candidate {
+                    bound: 0,
+                    item: Item::Node(root),
+                }]),
+            },
+            edit_distance: &self.edit_distance,
+            query,
+            max_edits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod fuzzy_search_nearest {
+        use crate::{bk::BkTree, distance::levenshtein, suggestion::Suggestion};
+
+        #[test]
+        fn test() {
+            let mut tree = BkTree::new(levenshtein);
+            for term in ["flood", "fold", "good", "foods", "food", "mood", "fool"] {
+                tree.insert(term.into());
+            }
+            let actual: Vec<Suggestion> = tree.fuzzy_search_nearest("fod", 2).collect();
+            assert_eq!(actual.len(), tree.fuzzy_search("fod", 2).count());
+            assert!(actual.windows(2).all(|w| w[0].edits <= w[1].edits));
+            assert!(actual
+                .iter()
+                .all(|s| levenshtein(&s.term, "fod") == s.edits));
+            assert_eq!(actual[0].edits, 1);
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::bk::{BkTree, Node};
+
+impl Node {
+    /// Builds a subtree from `choices` in insertion order,
+    /// which yields the same structure as inserting them one by one.
+    fn par_build<E>(mut choices: Vec<String>, edit_distance: &E) -> Option<Self>
+    where
+        E: Fn(&str, &str) -> usize + Sync,
+    {
+        if choices.is_empty() {
+            return None;
+        }
+        let term = choices.remove(0);
+
+        // Partition the remaining terms by their distance to this node.
+        let distances: Vec<Option<usize>> = choices
+            .par_iter()
+            .map(|choice| (choice != &term).then(|| (edit_distance)(&term, choice)))
+            .collect();
+        let mut count = 1;
+        let mut partitions = HashMap::<usize, Vec<String>>::new();
+        for (choice, dist) in choices.into_iter().zip(distances) {
+            match dist {
+                Some(dist) => partitions.entry(dist).or_default().push(choice),
+                None => count += 1,
+            }
+        }
+
+        let children = partitions
+            .into_par_iter()
+            .filter_map(|(dist, choices)| {
+                Node::par_build(choices, edit_distance).map(|child| (dist, child))
+            })
+            .collect();
+        Some(Self {
+            term,
+            count,
+            children,
+        })
+    }
+
+    fn par_search<'a, E>(&'a self, query: &str, max_edits: usize, edit_distance: &E) -> Vec<&'a str>
+    where
+        E: Fn(&str, &str) -> usize + Sync,
+    {
+        let edits = (edit_distance)(&self.term, query);
+        let (lower, upper) = (
+            edits.saturating_sub(max_edits),
+            edits.saturating_add(max_edits),
+        );
+        let mut ret: Vec<&str> = self
+            .children
+            .par_iter()
+            .filter(|(dist, _)| &lower <= *dist && *dist <= &upper)
+            .flat_map_iter(|(_, child)| child.par_search(query, max_edits, edit_distance))
+            .collect();
+        if edits <= max_edits {
+            ret.push(&self.term);
+        }
+        ret
+    }
+}
+
+impl<E: Fn(&str, &str) -> usize + Sync> BkTree<E> {
+    /// Builds a tree from `choices`, constructing independent subtrees concurrently.
+    ///
+    /// The resulting tree is the same as inserting `choices` in order with [`BkTree::insert`].
+    pub fn from_par_iter<I>(edit_distance: E, choices: I) -> Self
+    where
+        I: IntoParallelIterator<Item = String>,
+    {
+        let choices: Vec<String> = choices.into_par_iter().collect();
+        let root = Node::par_build(choices, &edit_distance);
+        Self {
+            len: root.as_ref().map_or(0, Node::len),
+            root,
+            edit_distance,
+        }
+    }
+
+    /// Same as [`BkTree::fuzzy_search`], but explores subtrees in parallel.
+    pub fn par_fuzzy_search(&self, query: &str, max_edits: usize) -> Vec<String> {
+        match &self.root {
+            None => vec![],
+            Some(root) => root
+                .par_search(query, max_edits, &self.edit_distance)
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod from_par_iter {
+        use crate::{bk::BkTree, distance::levenshtein};
+
+        #[test]
+        fn test() {
+            let choices = [
+                "apple",
+                "apply",
+                "ape",
+                "maple",
+                "apple",
+                "applet",
+                "pineapple",
+            ];
+            let mut tree = BkTree::new(levenshtein);
+            for term in choices {
+                tree.insert(term.into());
+            }
+            let par_tree = BkTree::from_par_iter(levenshtein, choices.map(String::from).to_vec());
+
+            let mut expected: Vec<String> = tree.fuzzy_search("appel", 2).collect();
+            let mut actual = par_tree.par_fuzzy_search("appel", 2);
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual);
+            assert_eq!(par_tree.count("apple"), 2);
+            assert_eq!(
+                par_tree
+                    .root
+                    .as_ref()
+                    .unwrap()
+                    .children
+                    .get(&1)
+                    .unwrap()
+                    .term,
+                "apply"
+            );
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::BTreeMap, mem};
+
+use crate::bk::{BkTree, Node};
+
+/// Shape of a [`BkTree`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TreeStats {
+    pub node_count: usize,
+    /// Depth of the deepest node, where the root is at depth 0.
+    pub max_depth: usize,
+    pub avg_depth: f64,
+    /// Number of nodes keyed by their number of children.
+    pub children_histogram: BTreeMap<usize, usize>,
+    /// Rough estimate of the heap and inline memory used by the nodes, in bytes.
+    pub memory_bytes: usize,
+}
+
+/// Work done by a single query, e.g. to compare against
+/// the `node_count` distance computations of a linear scan.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct QueryStats {
+    pub nodes_visited: usize,
+    pub distance_computations: usize,
+    /// Children skipped by the triangle inequality,
+    /// each of which prunes its whole subtree.
+    pub nodes_pruned: usize,
+}
+
+impl Node {
+    /// Heap memory owned by this node, excluding what its children own.
+    fn heap_bytes(&self) -> usize {
+        // hashbrown allocates one control byte per bucket.
+        self.term.capacity() + self.children.capacity() * (mem::size_of::<(usize, Node)>() + 1)
+    }
+}
+
+impl<E: Fn(&str, &str) -> usize> BkTree<E> {
+    /// Walks the whole tree to collect its [`TreeStats`].
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        let mut depth_sum = 0;
+        let mut stack = Vec::from_iter(self.root.iter().map(|root| (0, root)));
+        while let Some((depth, node)) = stack.pop() {
+            stats.node_count += 1;
+            stats.max_depth = stats.max_depth.max(depth);
+            depth_sum += depth;
+            *stats
+                .children_histogram
+                .entry(node.children.len())
+                .or_default() += 1;
+            stats.memory_bytes += node.heap_bytes();
+            stack.extend(node.children.values().map(|child| (depth + 1, child)));
+        }
+        if stats.node_count > 0 {
+            stats.avg_depth = depth_sum as f64 / stats.node_count as f64;
+        }
+        stats.memory_bytes += mem::size_of::<Self>();
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod stats {
+        use crate::{bk::BkTree, distance::levenshtein};
+
+        #[test]
+        fn test() {
+            let mut tree = BkTree::new(levenshtein);
+            for term in [
+                "book", "books", "cake", "boo", "cape", "boon", "cook", "cart",
+            ] {
+                tree.insert(term.into());
+            }
+            let stats = tree.stats();
+            assert_eq!(stats.node_count, tree.len());
+            assert_eq!(stats.node_count, 8);
+            assert_eq!(stats.children_histogram.values().sum::<usize>(), 8);
+            assert!(stats.max_depth >= 1);
+
+            let mut lookup = tree.fuzzy_search("bo", 1);
+            assert_eq!(lookup.by_ref().count(), 1);
+            let query = lookup.stats();
+            assert_eq!(query.nodes_visited, query.distance_computations);
+            assert!(query.nodes_visited + query.nodes_pruned <= 8);
+            assert!(query.nodes_pruned > 0);
+        }
+    }
+}
@@ -44,6 +44,8 @@
 //!     [this article](http://blog.notdot.net/2010/07/Damn-Cool-Algorithms-Levenshtein-Automata)
 //!     for the actual implementations.
 //! - [SymSpell](https://github.com/wolfgarbe/SymSpell)
+//! - [Vantage-point tree](https://en.wikipedia.org/wiki/Vantage-point_tree)
+//!   for real-valued metrics
 //!
 //! ## Installation
 //!
@@ -115,4 +117,6 @@ pub mod automata;
 pub mod basic;
 pub mod bk;
 pub mod distance;
+pub mod suggestion;
 pub mod symspell;
+pub mod vp;
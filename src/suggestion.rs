@@ -0,0 +1,28 @@
+use std::cmp::Ordering;
+
+/// A matched term along with its distance to the query
+/// and the number of times it was inserted.
+///
+/// Suggestions are ordered by ascending `edits`, then descending `count`,
+/// then by `term`, so sorting puts the best suggestion first.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Suggestion {
+    pub term: String,
+    pub edits: usize,
+    pub count: usize,
+}
+
+impl PartialOrd for Suggestion {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Suggestion {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.edits
+            .cmp(&other.edits)
+            .then_with(|| other.count.cmp(&self.count))
+            .then_with(|| self.term.cmp(&other.term))
+    }
+}
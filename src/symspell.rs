@@ -2,13 +2,30 @@ use std::{cmp, collections::HashSet};
 
 use anyhow::{ensure, Result};
 
+use crate::suggestion::Suggestion;
+
 mod dict;
 use dict::Dictionary;
 
+/// Which suggestions [`SymSpell::lookup`] returns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verbosity {
+    /// The best suggestion only.
+    Top,
+    /// All the suggestions at the smallest distance.
+    Closest,
+    /// All the suggestions within `max_edits`.
+    All,
+}
+
+/// `SymSpell<E>` is `Send` and `Sync` whenever `E` is;
+/// queries only need `&self`, but inserting needs `&mut self`.
 pub struct SymSpell<E: Fn(&str, &str) -> usize> {
     edit_distance: E,
     max_edits: usize,
     prefix_length: usize,
+    /// Terms inserted fewer times than this are not suggested.
+    min_count: usize,
 
     dictionary: Dictionary,
 }
@@ -27,6 +44,7 @@ impl<E: Fn(&str, &str) -> usize> SymSpell<E> {
             edit_distance,
             max_edits,
             prefix_length,
+            min_count: 1,
             dictionary: Dictionary::new(max_edits, prefix_length),
         })
     }
@@ -35,17 +53,60 @@ impl<E: Fn(&str, &str) -> usize> SymSpell<E> {
         SymSpell::new_with_prefix_length(edit_distance, max_edits, max_edits + 1).unwrap()
     }
 
+    /// Only suggests the terms inserted at least `min_count` times, e.g. to leave out typos
+    /// found in the corpus the dictionary is built from; the default is 1.
+    pub fn with_min_count(mut self, min_count: usize) -> Self {
+        self.min_count = min_count;
+        self
+    }
+
     pub fn insert(&mut self, choice: String) {
-        self.dictionary.insert(&choice);
+        self.insert_with_count(choice, 1);
+    }
+
+    /// Inserts `choice` as if it was inserted `count` times,
+    /// e.g. with its frequency in a corpus.
+    pub fn insert_with_count(&mut self, choice: String, count: usize) {
+        self.dictionary.insert(&choice, count);
+    }
+
+    /// Returns how many times `term` was inserted, or 0 if it is not in the dictionary.
+    pub fn count(&self, term: &str) -> usize {
+        self.dictionary.count(term)
     }
 
     pub fn fuzzy_search(&self, query: &str) -> Vec<String> {
+        self.search(query)
+            .into_iter()
+            .map(|suggestion| suggestion.term)
+            .collect()
+    }
+
+    /// Finds the terms within `max_edits` of `query` as [`SymSpell::fuzzy_search`],
+    /// ordered by distance and then by descending count, and keeps those
+    /// selected by `verbosity`.
+    pub fn lookup(&self, query: &str, verbosity: Verbosity) -> Vec<Suggestion> {
+        let mut ret = self.search(query);
+        ret.sort();
+        match verbosity {
+            Verbosity::Top => ret.truncate(1),
+            Verbosity::Closest => {
+                if let Some(edits) = ret.first().map(|s| s.edits) {
+                    ret.retain(|s| s.edits == edits);
+                }
+            }
+            Verbosity::All => (),
+        }
+        ret
+    }
+
+    fn search(&self, query: &str) -> Vec<Suggestion> {
         let mut ret = vec![];
 
         let (mut set1, mut set2) = (HashSet::new(), HashSet::new());
 
-        if self.dictionary.contains_term(query) {
-            ret.push(query.to_string());
+        if self.is_suggestible(query) {
+            ret.push(self.suggestion(query, 0));
         }
 
         set2.insert(query.to_string());
@@ -157,8 +218,8 @@ impl<E: Fn(&str, &str) -> usize> SymSpell<E> {
                         distance = (self.edit_distance)(query, suggestion);
                     }
 
-                    if distance <= self.max_edits {
-                        ret.push(suggestion.to_string())
+                    if distance <= self.max_edits && self.is_suggestible(suggestion) {
+                        ret.push(self.suggestion(suggestion, distance))
                     }
                 }
             }
@@ -181,6 +242,19 @@ impl<E: Fn(&str, &str) -> usize> SymSpell<E> {
         ret
     }
 
+    fn is_suggestible(&self, term: &str) -> bool {
+        let count = self.dictionary.count(term);
+        count > 0 && count >= self.min_count
+    }
+
+    fn suggestion(&self, term: &str, edits: usize) -> Suggestion {
+        Suggestion {
+            term: term.to_string(),
+            edits,
+            count: self.dictionary.count(term),
+        }
+    }
+
     fn condition(&self, query: &str, suggestion: &str, candidate: &str) -> bool {
         let min = if self.prefix_length - self.max_edits == candidate.len() {
             cmp::min(query.len(), suggestion.len()).saturating_sub(self.prefix_length)
@@ -200,3 +274,76 @@ impl<E: Fn(&str, &str) -> usize> SymSpell<E> {
                         != suggestion.as_bytes()[suggestion.len() - min - 1])))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    mod lookup {
+        use crate::{
+            distance::levenshtein,
+            suggestion::Suggestion,
+            symspell::{SymSpell, Verbosity},
+        };
+
+        fn suggestion(term: &str, edits: usize, count: usize) -> Suggestion {
+            Suggestion {
+                term: term.into(),
+                edits,
+                count,
+            }
+        }
+
+        #[test]
+        fn test() {
+            let mut sym = SymSpell::new(levenshtein, 2);
+            for (term, count) in [("food", 5), ("fool", 20), ("good", 50), ("flood", 1)] {
+                sym.insert_with_count(term.into(), count);
+            }
+            sym.insert("food".into());
+            assert_eq!(sym.count("food"), 6);
+            assert_eq!(sym.count("mood"), 0);
+
+            assert_eq!(
+                sym.lookup("fod", Verbosity::All),
+                vec![
+                    suggestion("food", 1, 6),
+                    suggestion("good", 2, 50),
+                    suggestion("fool", 2, 20),
+                    suggestion("flood", 2, 1),
+                ]
+            );
+            assert_eq!(
+                sym.lookup("foo", Verbosity::Closest),
+                vec![suggestion("fool", 1, 20), suggestion("food", 1, 6)]
+            );
+            assert_eq!(
+                sym.lookup("foo", Verbosity::Top),
+                vec![suggestion("fool", 1, 20)]
+            );
+            assert_eq!(
+                sym.lookup("food", Verbosity::Top),
+                vec![suggestion("food", 0, 6)]
+            );
+            assert_eq!(sym.lookup("xyz", Verbosity::Top), vec![]);
+        }
+
+        #[test]
+        fn test_min_count() {
+            let mut sym = SymSpell::new(levenshtein, 1).with_min_count(2);
+            sym.insert_with_count("food".into(), 5);
+            sym.insert("fod".into());
+            sym.insert_with_count("mood".into(), 0);
+            assert_eq!(
+                sym.lookup("fod", Verbosity::All),
+                vec![suggestion("food", 1, 5)]
+            );
+            assert_eq!(sym.fuzzy_search("mod"), Vec::<String>::new());
+
+            // Once inserted enough times, a term becomes suggestible.
+            sym.insert("fod".into());
+            assert_eq!(
+                sym.lookup("fod", Verbosity::All),
+                vec![suggestion("fod", 0, 2), suggestion("food", 1, 5)]
+            );
+        }
+    }
+}
@@ -3,7 +3,8 @@ use std::collections::{HashMap, HashSet};
 pub struct Dictionary {
     max_edits: usize,
     prefix_length: usize,
-    set: HashSet<String>,
+    /// Number of times each term was inserted.
+    counts: HashMap<String, usize>,
     /// Key (called candidate):
     /// A string with up to max_edits missing characters, derived from the term.
     /// Value (called suggestion):
@@ -16,22 +17,25 @@ impl Dictionary {
         Self {
             max_edits,
             prefix_length,
-            set: HashSet::new(),
+            counts: HashMap::new(),
             map: HashMap::new(),
         }
     }
 
-    pub fn contains_term(&self, term: &str) -> bool {
-        self.set.contains(term)
+    /// Returns how many times `term` was inserted, or 0 if it is not in the dictionary.
+    pub fn count(&self, term: &str) -> usize {
+        self.counts.get(term).copied().unwrap_or(0)
     }
 
     pub fn get_vec(&self, term: &str) -> Option<&Vec<String>> {
         self.map.get(term)
     }
 
-    pub fn insert(&mut self, term: &str) {
-        if !self.set.contains(term) {
-            self.set.insert(term.to_string());
+    pub fn insert(&mut self, term: &str, count: usize) {
+        if let Some(c) = self.counts.get_mut(term) {
+            *c = c.saturating_add(count);
+        } else {
+            self.counts.insert(term.to_string(), count);
 
             let len = term.len();
 
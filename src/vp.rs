@@ -0,0 +1,245 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+struct Node {
+    term: String,
+    /// Median distance from `term` to the terms below this node:
+    /// those `inside` are within `radius` and those `outside` are at least as far,
+    /// terms at exactly `radius` going either way so that both sides shrink.
+    radius: f64,
+    inside: Option<usize>,
+    outside: Option<usize>,
+}
+
+/// Builds the nodes with an explicit stack, as a metric with many ties
+/// (e.g. one that is 0 for equal terms and 1 otherwise) can make the tree deep.
+fn build<E: Fn(&str, &str) -> f64>(choices: Vec<String>, distance: &E) -> Vec<Node> {
+    let mut nodes: Vec<Node> = vec![];
+    // The choices below a node, and the parent to attach that node to,
+    // `true` for its inside.
+    let mut stack = vec![(choices, None::<(usize, bool)>)];
+    while let Some((mut choices, parent)) = stack.pop() {
+        let Some(term) = choices.pop() else {
+            continue;
+        };
+        let id = nodes.len();
+        match parent {
+            Some((parent, true)) => nodes[parent].inside = Some(id),
+            Some((parent, false)) => nodes[parent].outside = Some(id),
+            None => (),
+        }
+
+        let mut distances: Vec<(f64, String)> = choices
+            .into_iter()
+            .map(|choice| ((distance)(&term, &choice), choice))
+            .collect();
+        let (radius, outside) = match distances.len() {
+            0 => (0., vec![]),
+            len => {
+                let median = len / 2;
+                distances.select_nth_unstable_by(median, |a, b| a.0.total_cmp(&b.0));
+                (distances[median].0, distances.split_off(median + 1))
+            }
+        };
+        let strip = |v: Vec<(f64, String)>| v.into_iter().map(|(_, choice)| choice).collect();
+        nodes.push(Node {
+            term,
+            radius,
+            inside: None,
+            outside: None,
+        });
+        stack.push((strip(distances), Some((id, true))));
+        stack.push((strip(outside), Some((id, false))));
+    }
+    nodes
+}
+
+/// A [vantage-point tree](https://en.wikipedia.org/wiki/Vantage-point_tree)
+/// over any real-valued metric, e.g. normalized or weighted edit distances.
+///
+/// Unlike [`BkTree`](crate::bk::BkTree), the tree is built once from all the choices.
+///
+/// `VpTree<E>` is `Send` and `Sync` whenever `E` is.
+pub struct VpTree<E: Fn(&str, &str) -> f64> {
+    /// The root is the first node, if any.
+    nodes: Vec<Node>,
+    distance: E,
+}
+
+/// A term and its distance to the query, ordered by distance.
+struct Neighbor<'a>(f64, &'a str);
+
+impl PartialEq for Neighbor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Neighbor<'_> {}
+
+impl PartialOrd for Neighbor<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Neighbor<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then_with(|| self.1.cmp(other.1))
+    }
+}
+
+impl<E: Fn(&str, &str) -> f64> VpTree<E> {
+    pub fn new<I: IntoIterator<Item = String>>(distance: E, choices: I) -> Self {
+        let mut choices: Vec<String> = choices.into_iter().collect();
+        choices.sort();
+        choices.dedup();
+        Self {
+            nodes: build(choices, &distance),
+            distance,
+        }
+    }
+
+    /// Finds the terms whose distance to `query` is at most `max_distance`.
+    pub fn fuzzy_search(&self, query: &str, max_distance: f64) -> Vec<String> {
+        let mut ret = vec![];
+        let mut stack = Vec::from_iter(self.nodes.first());
+        while let Some(node) = stack.pop() {
+            let dist = (self.distance)(&node.term, query);
+            if dist <= max_distance {
+                ret.push(node.term.to_string());
+            }
+            // By the triangle inequality, terms inside are at least
+            // `dist - radius` away from the query and terms outside
+            // at least `radius - dist`.
+            if let Some(inside) = node.inside {
+                if dist - max_distance <= node.radius {
+                    stack.push(&self.nodes[inside]);
+                }
+            }
+            if let Some(outside) = node.outside {
+                if dist + max_distance >= node.radius {
+                    stack.push(&self.nodes[outside]);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Finds the `k` terms nearest to `query`, nearest first.
+    pub fn nearest(&self, query: &str, k: usize) -> Vec<String> {
+        // A max-heap of the nearest `k` terms found so far,
+        // so its top is the radius within which the remaining terms must lie.
+        let mut best = BinaryHeap::with_capacity(k.min(self.nodes.len()) + 1);
+        let tau = |best: &BinaryHeap<Neighbor>| match best.len() < k {
+            true => f64::INFINITY,
+            false => best.peek().unwrap().0,
+        };
+
+        // Nodes to visit along with a lower bound of the distance to the query
+        // of every term below them, by the triangle inequality.
+        let mut stack = match (self.nodes.first(), k > 0) {
+            (Some(root), true) => vec![(root, 0.)],
+            _ => vec![],
+        };
+        while let Some((node, bound)) = stack.pop() {
+            // Terms as far as `tau` may still replace the top on their order.
+            if bound > tau(&best) {
+                continue;
+            }
+            let neighbor = Neighbor((self.distance)(&node.term, query), &node.term);
+            let dist = neighbor.0;
+            if best.len() < k || neighbor < *best.peek().unwrap() {
+                best.push(neighbor);
+                if best.len() > k {
+                    best.pop();
+                }
+            }
+
+            // Visit the side the query falls in first, which shrinks `tau` early,
+            // then the other side only if it can still hold a nearer term.
+            let inside = node
+                .inside
+                .map(|i| (&self.nodes[i], (dist - node.radius).max(0.)));
+            let outside = node
+                .outside
+                .map(|i| (&self.nodes[i], (node.radius - dist).max(0.)));
+            match dist <= node.radius {
+                true => stack.extend(outside.into_iter().chain(inside)),
+                false => stack.extend(inside.into_iter().chain(outside)),
+            }
+        }
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|Neighbor(_, term)| term.to_string())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distance::levenshtein;
+
+    fn normalized(lhs: &str, rhs: &str) -> f64 {
+        let len = lhs.chars().count().max(rhs.chars().count()).max(1);
+        levenshtein(lhs, rhs) as f64 / len as f64
+    }
+
+    const CHOICES: [&str; 10] = [
+        "food", "foods", "fool", "flood", "fold", "good", "wood", "mood", "apple", "feed",
+    ];
+
+    /// 0 for equal terms and 1 otherwise, so every distance is tied.
+    fn discrete(lhs: &str, rhs: &str) -> f64 {
+        match lhs == rhs {
+            true => 0.,
+            false => 1.,
+        }
+    }
+
+    mod new {
+        use super::discrete;
+        use crate::vp::VpTree;
+
+        #[test]
+        fn test_ties() {
+            let tree = VpTree::new(discrete, (0..200_000).map(|i| i.to_string()));
+            assert_eq!(tree.fuzzy_search("1234", 0.), vec!["1234"]);
+            assert_eq!(tree.fuzzy_search("1234", 1.).len(), 200_000);
+            assert_eq!(tree.nearest("1234", 2), vec!["1234", "0"]);
+            assert_eq!(tree.nearest("x", 5).len(), 5);
+        }
+    }
+
+    mod fuzzy_search {
+        use super::{normalized, CHOICES};
+        use crate::vp::VpTree;
+
+        #[test]
+        fn test() {
+            let tree = VpTree::new(normalized, CHOICES.map(String::from));
+            let mut expected: Vec<&str> = CHOICES
+                .into_iter()
+                .filter(|c| normalized("fod", c) <= 0.5)
+                .collect();
+            let mut actual = tree.fuzzy_search("fod", 0.5);
+            expected.sort();
+            actual.sort();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    mod nearest {
+        use super::{normalized, CHOICES};
+        use crate::vp::VpTree;
+
+        #[test]
+        fn test() {
+            let tree = VpTree::new(normalized, CHOICES.map(String::from));
+            assert_eq!(tree.nearest("fod", 1), vec!["fold"]);
+            assert_eq!(tree.nearest("fod", 3), vec!["fold", "food", "flood"]);
+            assert_eq!(tree.nearest("fod", 100).len(), CHOICES.len());
+            assert_eq!(tree.nearest("fod", usize::MAX).len(), CHOICES.len());
+            assert_eq!(tree.nearest("fod", 1 << 40).len(), CHOICES.len());
+        }
+    }
+}
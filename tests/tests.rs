@@ -5,8 +5,14 @@ use std::{
 };
 
 use fuzzy_search::{
-    automata::LevenshteinAutomata, basic::fuzzy_search, bk::BkTree, distance::levenshtein,
-    symspell::SymSpell,
+    automata::{
+        ApproximateRegex, FuzzyPattern, IncrementalSearch, LevenshteinAutomata,
+        MultiLevenshteinAutomata,
+    },
+    basic::fuzzy_search,
+    bk::BkTree,
+    distance::levenshtein,
+    symspell::{SymSpell, Verbosity},
 };
 
 #[doc(hidden)]
@@ -31,6 +37,90 @@ fn test_levenshtein_automata() {
     );
 }
 
+#[test]
+fn test_levenshtein_automata_with_equivalence() {
+    let choices = load_choices(true);
+    let automata =
+        LevenshteinAutomata::with_equivalence("FOOD", 2, |ch| ch.to_lowercase().next().unwrap());
+    assert_eq!(automata.fuzzy_search(&choices).len(), 388);
+}
+
+#[test]
+fn test_levenshtein_automata_utf8() {
+    let choices = load_choices(true);
+    let utf8 = LevenshteinAutomata::new("food", 2).to_utf8().unwrap();
+    assert_eq!(utf8.fuzzy_search(&choices).len(), 388);
+}
+
+#[test]
+fn test_incremental_search() {
+    let choices = load_choices(true);
+    let mut search = IncrementalSearch::new(&choices, 2);
+    for ch in "foodstuffs".chars() {
+        search.push(ch);
+        assert_eq!(
+            search.fuzzy_search(),
+            LevenshteinAutomata::new_prefix(search.query(), 2).fuzzy_search(&choices)
+        );
+    }
+}
+
+#[test]
+fn test_multi_levenshtein_automata() {
+    let choices = load_choices(true);
+    let matches = MultiLevenshteinAutomata::new([("food", 2), ("good", 1)]).fuzzy_search(&choices);
+    assert_eq!(
+        matches.iter().filter(|(_, query, _)| *query == 0).count(),
+        388
+    );
+    assert_eq!(
+        matches.iter().filter(|(_, query, _)| *query == 1).count(),
+        LevenshteinAutomata::new("good", 1)
+            .fuzzy_search(&choices)
+            .len()
+    );
+}
+
+#[test]
+fn test_fuzzy_pattern() {
+    let choices = load_choices(true);
+    assert_eq!(
+        FuzzyPattern::new("food*", 0)
+            .unwrap()
+            .fuzzy_search(&choices),
+        choices
+            .iter()
+            .filter(|c| c.starts_with("food"))
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(
+        FuzzyPattern::new("food", 2)
+            .unwrap()
+            .fuzzy_search(&choices)
+            .len(),
+        388
+    );
+}
+
+#[test]
+fn test_approximate_regex() {
+    let choices = load_choices(true);
+    let regex = ApproximateRegex::new("(f|g)oods?", 1).unwrap();
+    assert_eq!(
+        regex.fuzzy_search(&choices),
+        choices
+            .iter()
+            .filter(|c| {
+                ["food", "foods", "good", "goods"]
+                    .iter()
+                    .any(|q| levenshtein(q, c) <= 1)
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn test_levenshtein() {
     let choices = load_choices(false);
@@ -47,6 +137,12 @@ fn test_bk_tree() {
     assert_eq!(bk.fuzzy_search("food", 2).count(), 388)
 }
 
+#[test]
+fn test_par_bk_tree() {
+    let bk = BkTree::from_par_iter(levenshtein, load_choices(false));
+    assert_eq!(bk.par_fuzzy_search("food", 2).len(), 388)
+}
+
 #[test]
 fn test_symspell() {
     let mut sym = SymSpell::new(levenshtein, 2);
@@ -56,3 +152,17 @@ fn test_symspell() {
     }
     assert_eq!(sym.fuzzy_search("food").len(), 388)
 }
+
+#[test]
+fn test_symspell_lookup() {
+    let mut sym = SymSpell::new(levenshtein, 2);
+    for t in load_choices(false).into_iter() {
+        sym.insert(t);
+    }
+    let all = sym.lookup("food", Verbosity::All);
+    assert_eq!(all.len(), 388);
+    assert!(all.windows(2).all(|w| w[0] <= w[1]));
+    let closest = sym.lookup("food", Verbosity::Closest);
+    assert!(closest.iter().all(|s| s.edits == all[0].edits));
+    assert_eq!(sym.lookup("food", Verbosity::Top), all[..1]);
+}